@@ -2,10 +2,21 @@
 
 mod neat;
 
-use neat::{tst, Conf, Pop};
+use neat::{
+    tst, Checkpointer, Conf, CsvReporter, DotOptions, GenerationStats, InnovationRegistry,
+    JsonLinesReporter, LoadError, NeatError, Net, Pop, Reporter, StdoutReporter, Termination,
+};
 
 struct NEATConf {
     gens: u32,
+    /// Dump the champion as a DOT graph every this many generations. Set with `--dot-every N`.
+    dot_every: Option<u32>,
+    /// Seeds the population so the run can be reproduced. Set with `--seed N`.
+    seed: Option<u64>,
+    /// The number of evaluation threads, 0 for every core. Set with `--threads N`.
+    threads: usize,
+    /// Adapts the compatibility threshold towards this many species. Set with `--target-species N`.
+    target_species: Option<usize>,
 }
 
 impl Conf for NEATConf {
@@ -21,56 +32,136 @@ impl Conf for NEATConf {
     fn get_link_disable_mutation_prob(&self) -> f64 {
         0.4
     }
+    fn get_threads_count(&self) -> usize {
+        self.threads
+    }
+    fn get_target_species_count(&self) -> Option<usize> {
+        self.target_species
+    }
 }
 
 fn square(a: f64) -> f64 {
     a * a
 }
 
-fn main() {
-    let conf = NEATConf { gens: 40 };
+/// Scores how well the network computes XOR. The best fitness is 4.
+fn xor_fitness(net: &mut Net) -> Result<f64, NeatError> {
+    let mut outputs = [0.0; 4];
+    net.eval_batch(&[0.0, 1.0, 1.0, 0.0, 1.0, 1.0, 0.0, 0.0], &mut outputs)?;
+    let [net01, net10, net11, net00] = outputs;
 
-    let mut pop = Pop::new(150, 2, 1, &conf);
-    for i in 0..conf.gens {
-        let mut fitness_sum = 0.0;
-        let mut best_fitness = 0.0;
+    Ok(4.0 - (square(1.0 - net01) + square(1.0 - net10) + square(net11) + square(net00)))
+}
 
-        let mut links_count_sum = 0;
-        let mut hidden_node_count_sum = 0;
+/// Dumps the champion of every few generations as a DOT graph.
+struct DotDumper {
+    every: Option<u32>,
+    last_gen: u32,
+}
+
+impl Reporter for DotDumper {
+    fn generation_end(&mut self, pop: &Pop, stats: &GenerationStats) -> std::io::Result<()> {
+        let i = stats.generation;
+        if let Some(every) = self.every {
+            if i.is_multiple_of(every) || i == self.last_gen {
+                let best_net = pop
+                    .nets
+                    .iter()
+                    .max_by(|a, b| a.fitness.total_cmp(&b.fitness))
+                    .unwrap();
+                let path = format!("champion_gen_{}.dot", i);
+                let dot = best_net.to_dot(&DotOptions::default());
+                if let Err(err) = std::fs::write(&path, dot) {
+                    eprintln!("Failed to write {}: {}", path, err);
+                }
+            }
+        }
+        Ok(())
+    }
+}
 
-        for net in &mut pop.nets {
-            links_count_sum += net.get_enabled_links_count();
-            hidden_node_count_sum += net.get_hidden_nodes_count();
+fn main() -> Result<(), NeatError> {
+    let mut args = std::env::args().skip(1);
+    let mut dot_every = None;
+    let mut seed = None;
+    let mut threads = 1;
+    let mut target_species = None;
+    let mut checkpoint_every = 0;
+    let mut resume = false;
+    let mut csv_path = None;
+    let mut jsonl_path = None;
+    while let Some(arg) = args.next() {
+        if arg == "--dot-every" {
+            dot_every = args.next().and_then(|n| n.parse().ok()).filter(|n| 0 < *n);
+        } else if arg == "--seed" {
+            seed = args.next().and_then(|n| n.parse().ok());
+        } else if arg == "--threads" {
+            threads = args.next().and_then(|n| n.parse().ok()).unwrap_or(threads);
+        } else if arg == "--target-species" {
+            target_species = args.next().and_then(|n| n.parse().ok()).filter(|n| 0 < *n);
+        } else if arg == "--checkpoint-every" {
+            checkpoint_every = args.next().and_then(|n| n.parse().ok()).unwrap_or(0);
+        } else if arg == "--resume" {
+            resume = true;
+        } else if arg == "--csv" {
+            csv_path = args.next();
+        } else if arg == "--jsonl" {
+            jsonl_path = args.next();
+        }
+    }
 
-            let net01 = net.eval(&[0.0, 1.0], &pop.innovs)[0];
-            let net10 = net.eval(&[1.0, 0.0], &pop.innovs)[0];
-            let net11 = net.eval(&[1.0, 1.0], &pop.innovs)[0];
-            let net00 = net.eval(&[0.0, 0.0], &pop.innovs)[0];
+    let conf = NEATConf {
+        gens: 40,
+        dot_every,
+        seed,
+        threads,
+        target_species,
+    };
 
-            net.fitness =
-                4.0 - (square(1.0 - net01) + square(1.0 - net10) + square(net11) + square(net00));
-            best_fitness = if best_fitness < net.fitness {
-                net.fitness
-            } else {
-                best_fitness
-            };
-            fitness_sum += net.fitness;
+    // Checkpoints go to `checkpoints/` with `--checkpoint-every N`, and `--resume` continues from
+    // the newest one.
+    let mut checkpointer = Checkpointer::new("checkpoints", checkpoint_every, 3);
+    let latest_checkpoint = if resume {
+        checkpointer.latest().map_err(LoadError::from)?
+    } else {
+        None
+    };
+    let mut pop = match (latest_checkpoint, conf.seed) {
+        (Some(path), _) => {
+            println!("Resuming from {}", path.display());
+            Pop::load_checkpoint(path, &[])?
         }
+        (None, Some(seed)) => Pop::with_seed(150, 2, 1, seed, &conf),
+        (None, None) => Pop::new(150, 2, 1, &conf),
+    };
+    let mut stdout_reporter = StdoutReporter;
+    let mut dot_dumper = DotDumper {
+        every: conf.dot_every,
+        last_gen: conf.gens.saturating_sub(1),
+    };
+    let mut reporters: Vec<&mut dyn Reporter> =
+        vec![&mut stdout_reporter, &mut dot_dumper, &mut checkpointer];
 
-        //if i % 15 == 14 {
-        println!("Gen {}", i);
-        println!("\tspecies count: {}", pop.species.len());
-        println!(
-            "\tavarage hidden nodes count: {}",
-            (hidden_node_count_sum as f64) / (pop.size as f64)
-        );
-        println!(
-            "\tavarage links count: {}",
-            (links_count_sum as f64) / (pop.size as f64)
-        );
-        println!("\tavarage fitness: {}", fitness_sum / (pop.size as f64));
-        println!("\tbest fitness: {}", best_fitness);
-        //}
-        pop.next_gen(&conf);
+    let mut csv_reporter = csv_path
+        .map(|path| std::fs::File::create(path).map(CsvReporter::new))
+        .transpose()?;
+    if let Some(csv_reporter) = &mut csv_reporter {
+        reporters.push(csv_reporter);
+    }
+    let mut jsonl_reporter = jsonl_path
+        .map(|path| std::fs::File::create(path).map(JsonLinesReporter::new))
+        .transpose()?;
+    if let Some(jsonl_reporter) = &mut jsonl_reporter {
+        reporters.push(jsonl_reporter);
     }
+
+    if pop.generation < conf.gens {
+        let termination = Termination {
+            max_generations: Some(conf.gens - pop.generation),
+            ..Termination::default()
+        };
+        pop.run(&xor_fitness, &termination, &mut reporters, &conf)?;
+    }
+
+    Ok(())
 }
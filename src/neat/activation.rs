@@ -0,0 +1,100 @@
+use std::fmt;
+
+/// A user-registered activation function. Two custom activations are the same if their names match.
+#[derive(Clone, Copy)]
+pub struct CustomActivation {
+    pub name: &'static str,
+    pub func: fn(f64) -> f64,
+}
+
+impl PartialEq for CustomActivation {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl fmt::Debug for CustomActivation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CustomActivation({})", self.name)
+    }
+}
+
+/// The activation function of a node.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum Activation {
+    /// The steepened sigmoid `1 / (1 + exp(-4.9x))` from the NEAT paper.
+    #[default]
+    Sigmoid,
+    Tanh,
+    Relu,
+    Identity,
+    Sine,
+    /// `exp(-x^2)`
+    Gaussian,
+    Abs,
+    /// 1 for positive values, 0 otherwise.
+    Step,
+    Custom(CustomActivation),
+}
+
+impl Activation {
+    /// Registers a custom activation function under `name`.
+    pub fn custom(name: &'static str, func: fn(f64) -> f64) -> Self {
+        Activation::Custom(CustomActivation { name, func })
+    }
+
+    /// Applies the activation function.
+    pub fn apply(self, x: f64) -> f64 {
+        match self {
+            Activation::Sigmoid => 1.0 / (1.0 + (-4.9 * x).exp()),
+            Activation::Tanh => x.tanh(),
+            Activation::Relu => x.max(0.0),
+            Activation::Identity => x,
+            Activation::Sine => x.sin(),
+            Activation::Gaussian => (-x * x).exp(),
+            Activation::Abs => x.abs(),
+            Activation::Step => {
+                if 0.0 < x {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Activation::Custom(custom) => (custom.func)(x),
+        }
+    }
+
+    /// The name of the activation function.
+    pub fn name(self) -> &'static str {
+        match self {
+            Activation::Sigmoid => "sigmoid",
+            Activation::Tanh => "tanh",
+            Activation::Relu => "relu",
+            Activation::Identity => "identity",
+            Activation::Sine => "sin",
+            Activation::Gaussian => "gauss",
+            Activation::Abs => "abs",
+            Activation::Step => "step",
+            Activation::Custom(custom) => custom.name,
+        }
+    }
+
+    /// Finds an activation function by its name. Custom activations are looked up in `custom`.
+    pub fn from_name(name: &str, custom: &[Activation]) -> Option<Self> {
+        let builtin = [
+            Activation::Sigmoid,
+            Activation::Tanh,
+            Activation::Relu,
+            Activation::Identity,
+            Activation::Sine,
+            Activation::Gaussian,
+            Activation::Abs,
+            Activation::Step,
+        ];
+        builtin
+            .iter()
+            .chain(custom)
+            .find(|activation| activation.name() == name)
+            .copied()
+    }
+}
@@ -0,0 +1,190 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::neat::{Activation, LoadError, NeatRng, Pop, FORMAT_VERSION};
+
+use super::serialization::{read_binary_header, ArchiveData};
+
+/// The first bytes of a checkpoint, followed by the version as a little endian `u32`.
+const CHECKPOINT_MAGIC: &[u8; 4] = b"NCKP";
+
+#[derive(Serialize, Deserialize)]
+struct CheckpointData {
+    size: usize,
+    generation: u32,
+    compat_threshold: f64,
+    rng: NeatRng,
+    archive: ArchiveData,
+}
+
+impl Pop {
+    /// Serializes the whole state of the population, including the generator, so that a loaded
+    /// population evolves exactly like this one would.
+    pub fn to_checkpoint_bytes(&self) -> Vec<u8> {
+        let data = CheckpointData {
+            size: self.size,
+            generation: self.generation,
+            compat_threshold: self.compat_threshold,
+            rng: self.rng.clone(),
+            archive: ArchiveData::new(&self.innovs, &self.nets, &self.species),
+        };
+
+        let mut out = CHECKPOINT_MAGIC.to_vec();
+        out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        bincode::serialize_into(&mut out, &data).expect("Populations are always serializable.");
+        out
+    }
+
+    /// Loads and validates a population from `Pop::to_checkpoint_bytes`. Custom activations are
+    /// looked up by name in `custom_activations`.
+    pub fn from_checkpoint_bytes(
+        bytes: &[u8],
+        custom_activations: &[Activation],
+    ) -> Result<Self, LoadError> {
+        let payload = read_binary_header(bytes, CHECKPOINT_MAGIC)?;
+        let data: CheckpointData =
+            bincode::deserialize(payload).map_err(|err| LoadError::Syntax(err.to_string()))?;
+        let archive = data.archive.into_archive(custom_activations)?;
+
+        Ok(Self {
+            size: data.size,
+            generation: data.generation,
+            nets: archive.nets,
+            species: archive.species,
+            innovs: archive.innovs,
+            compat_threshold: data.compat_threshold,
+            rng: data.rng,
+        })
+    }
+
+    /// Writes a checkpoint of the population to a file.
+    pub fn save_checkpoint(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        // Writing to a temporary file first keeps the old checkpoint intact if the process dies
+        // mid-write.
+        let path = path.as_ref();
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, self.to_checkpoint_bytes())?;
+        fs::rename(tmp_path, path)
+    }
+
+    /// Reads a checkpoint written by `Pop::save_checkpoint`.
+    pub fn load_checkpoint(
+        path: impl AsRef<Path>,
+        custom_activations: &[Activation],
+    ) -> Result<Self, LoadError> {
+        Self::from_checkpoint_bytes(&fs::read(path)?, custom_activations)
+    }
+}
+
+/// Saves a checkpoint every few generations into a directory and removes the old ones.
+///
+/// The checkpoints are named `checkpoint_gen_{generation}.bin`.
+pub struct Checkpointer {
+    pub dir: PathBuf,
+    /// Saves when the generation counter is a multiple of this.
+    pub every: u32,
+    /// The number of newest checkpoints to keep. 0 keeps all of them.
+    pub keep: usize,
+}
+
+impl Checkpointer {
+    pub fn new(dir: impl Into<PathBuf>, every: u32, keep: usize) -> Self {
+        Self {
+            dir: dir.into(),
+            every,
+            keep,
+        }
+    }
+
+    /// Saves a checkpoint if the population is at a checkpoint generation, and returns its path.
+    pub fn checkpoint(&self, pop: &Pop) -> io::Result<Option<PathBuf>> {
+        if self.every == 0 || !pop.generation.is_multiple_of(self.every) {
+            return Ok(None);
+        }
+
+        fs::create_dir_all(&self.dir)?;
+        let path = self
+            .dir
+            .join(format!("checkpoint_gen_{}.bin", pop.generation));
+        pop.save_checkpoint(&path)?;
+
+        if 0 < self.keep {
+            let checkpoints = self.list()?;
+            if self.keep < checkpoints.len() {
+                for (_, old_path) in &checkpoints[..(checkpoints.len() - self.keep)] {
+                    fs::remove_file(old_path)?;
+                }
+            }
+        }
+
+        Ok(Some(path))
+    }
+
+    /// The newest checkpoint in the directory, to resume from.
+    pub fn latest(&self) -> io::Result<Option<PathBuf>> {
+        Ok(self.list()?.pop().map(|(_, path)| path))
+    }
+
+    /// The checkpoints in the directory, oldest first.
+    fn list(&self) -> io::Result<Vec<(u32, PathBuf)>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+
+        let mut out = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            let generation = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix("checkpoint_gen_"))
+                .and_then(|name| name.strip_suffix(".bin"))
+                .and_then(|generation| generation.parse().ok());
+            if let Some(generation) = generation {
+                out.push((generation, path));
+            }
+        }
+        out.sort_unstable();
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::neat::{Checkpointer, Pop, TestConf};
+
+    fn next_gens(pop: &mut Pop, count: usize, conf: &TestConf) {
+        for _ in 0..count {
+            for net in &mut pop.nets {
+                net.fitness = 1.0 + net.eval(&[1.0, 0.5]).unwrap()[0];
+            }
+            pop.next_gen(conf).unwrap();
+        }
+    }
+
+    #[test]
+    fn resumed_run_matches_uninterrupted_run() {
+        let conf = TestConf { threads: 1 };
+        let mut uninterrupted = Pop::with_seed(60, 2, 1, 3, &conf);
+        next_gens(&mut uninterrupted, 8, &conf);
+
+        let dir = std::env::temp_dir().join(format!("neat_checkpoint_test_{}", std::process::id()));
+        let checkpointer = Checkpointer::new(&dir, 4, 1);
+        let mut interrupted = Pop::with_seed(60, 2, 1, 3, &conf);
+        next_gens(&mut interrupted, 4, &conf);
+        checkpointer.checkpoint(&interrupted).unwrap();
+        let path = checkpointer.latest().unwrap().unwrap();
+        let mut resumed = Pop::load_checkpoint(path, &[]).unwrap();
+        std::fs::remove_dir_all(dir).unwrap();
+        next_gens(&mut resumed, 4, &conf);
+
+        assert_eq!(resumed.generation, uninterrupted.generation);
+        assert!(resumed.to_checkpoint_bytes() == uninterrupted.to_checkpoint_bytes());
+    }
+}
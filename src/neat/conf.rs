@@ -1,5 +1,13 @@
+use rand::RngCore;
+
+use crate::neat::{
+    Activation, CrossoverMethod, FitnessTransform, InnovationScope, Roulette, SelectionStrategy,
+};
+
+/// The parameters of the evolution. Configurations are shared between threads, so they have to be
+/// `Send` and `Sync`.
 #[allow(unused_variables)]
-pub trait Conf {
+pub trait Conf: Send + Sync {
     fn get_excess_coef(&self) -> f64 {
         1.0
     }
@@ -9,9 +17,26 @@ pub trait Conf {
     fn get_weight_diff_coef(&self) -> f64 {
         0.4
     }
+    fn get_activation_diff_coef(&self) -> f64 {
+        1.0
+    }
+    /// The compatibility threshold, or the initial one when it's adaptive.
     fn get_compat_threshold(&self) -> f64 {
         3.0
     }
+    /// Makes the compatibility threshold adaptive. Every generation the population raises it by
+    /// `get_compat_threshold_step` if there are more species than this, and lowers it if there are
+    /// fewer. `None` keeps it fixed.
+    fn get_target_species_count(&self) -> Option<usize> {
+        None
+    }
+    fn get_compat_threshold_step(&self) -> f64 {
+        0.3
+    }
+    /// The lowest and the highest adaptive compatibility threshold.
+    fn get_compat_threshold_bounds(&self) -> (f64, f64) {
+        (0.3, 30.0)
+    }
     fn size_norm(&self, size1: usize, size2: usize) -> f64 {
         1.0
     }
@@ -31,6 +56,24 @@ pub trait Conf {
     fn get_complete_weight_override_prob(&self) -> f64 {
         0.1
     }
+    fn get_activation_mutation_prob(&self) -> f64 {
+        0.1
+    }
+    fn get_bias_mutation_prob(&self) -> f64 {
+        0.8
+    }
+    fn get_response_mutation_prob(&self) -> f64 {
+        0.1
+    }
+
+    /// The activation function of new hidden and output nodes.
+    fn get_default_activation(&self) -> Activation {
+        Activation::Sigmoid
+    }
+    /// The activation functions that the activation mutation chooses from.
+    fn get_allowed_activations(&self) -> Vec<Activation> {
+        vec![self.get_default_activation()]
+    }
 
     fn link_enabling_in_child_prob(&self) -> f64 {
         0.25
@@ -38,24 +81,102 @@ pub trait Conf {
     fn get_crossover_prob(&self) -> f64 {
         0.75
     }
+    /// How the genes that both parents have are inherited.
+    fn get_crossover_method(&self) -> CrossoverMethod {
+        CrossoverMethod::Uniform
+    }
+    /// Which earlier innovations a new link reuses when it connects the same nodes.
+    ///
+    /// `Pop::collect_innovations` drops the innovations that no net uses anymore every generation,
+    /// so even `InnovationScope::Global` only reuses the innovations that some net still has.
+    fn get_innovation_scope(&self) -> InnovationScope {
+        InnovationScope::Generation
+    }
+    /// How the parents of a child are chosen from the members of its species.
+    fn get_selection_strategy(&self) -> &dyn SelectionStrategy {
+        &Roulette
+    }
+
+    /// Allows cyclic and self links. The networks then become stateful, see `Net::eval_step`.
+    fn allow_recurrent_links(&self) -> bool {
+        false
+    }
 
-    fn init_weight(&self) -> f64 {
-        use rand::{prelude::*, thread_rng};
+    fn init_weight(&self, rng: &mut dyn RngCore) -> f64 {
+        use rand::prelude::*;
         use rand_distr::StandardNormal;
 
-        StandardNormal {}.sample(&mut thread_rng())
+        StandardNormal {}.sample(rng)
     }
-    fn mutate_weight(&self, weight: &mut f64) {
-        use rand::{prelude::*, thread_rng};
+    fn mutate_weight(&self, weight: &mut f64, rng: &mut dyn RngCore) {
+        use rand::prelude::*;
         use rand_distr::Normal;
 
-        *weight = Normal::new(0.0, 0.5).unwrap().sample(&mut thread_rng());
+        *weight = Normal::new(0.0, 0.5).unwrap().sample(rng);
     }
 
+    /// Gives every hidden and output node its own bias gene. Otherwise the bias comes only from the
+    /// links of the bias node.
+    fn use_node_bias(&self) -> bool {
+        false
+    }
+    /// Gives every hidden and output node a response gene that scales the sum of its inputs.
+    fn use_node_response(&self) -> bool {
+        false
+    }
+    fn init_bias(&self, rng: &mut dyn RngCore) -> f64 {
+        use rand::prelude::*;
+        use rand_distr::StandardNormal;
+
+        StandardNormal {}.sample(rng)
+    }
+    fn mutate_bias(&self, bias: &mut f64, rng: &mut dyn RngCore) {
+        use rand::prelude::*;
+        use rand_distr::Normal;
+
+        *bias += Normal::new(0.0, 0.5).unwrap().sample(rng);
+    }
+    fn init_response(&self, rng: &mut dyn RngCore) -> f64 {
+        1.0
+    }
+    fn mutate_response(&self, response: &mut f64, rng: &mut dyn RngCore) {
+        use rand::prelude::*;
+        use rand_distr::Normal;
+
+        *response += Normal::new(0.0, 0.1).unwrap().sample(rng);
+    }
+
+    /// How the fitness is transformed before it's shared within the species. Fitness-proportionate
+    /// selection and the offspring allocation work best with positive values, so problems with
+    /// negative or unbounded fitness should use anything but `FitnessTransform::Identity`.
+    fn get_fitness_transform(&self) -> FitnessTransform {
+        FitnessTransform::Identity
+    }
     fn get_cull_survival_percentage(&self) -> f64 {
         0.6
     }
     fn get_staleness_threshold(&self) -> u32 {
         15
     }
+
+    /// The number of best members of every species that are copied unchanged into the next
+    /// generation. They count towards the offspring of the species.
+    fn get_species_elitism(&self) -> usize {
+        0
+    }
+    /// Species elitism only applies to species that had at least this many members.
+    fn get_species_elitism_min_size(&self) -> usize {
+        5
+    }
+    /// Copies the best net of the population unchanged into the next generation, and keeps its
+    /// species from being removed for staleness.
+    fn keep_champion(&self) -> bool {
+        false
+    }
+
+    /// The number of threads that `Pop::run` evaluates the population with and that `Pop::next_gen`
+    /// breeds it with. 0 uses every available core.
+    fn get_threads_count(&self) -> usize {
+        1
+    }
 }
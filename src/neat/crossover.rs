@@ -0,0 +1,359 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeSet, HashMap, HashSet},
+};
+
+use rand::prelude::*;
+use rand_distr::Uniform;
+
+use crate::neat::{Conf, Net};
+
+use super::neuralnet::Node;
+
+/// How the genes that both parents have are inherited, see `Conf::get_crossover_method`.
+///
+/// Disjoint and excess genes always come from the fitter parent, or from both when the fitness is
+/// equal.
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub enum CrossoverMethod {
+    /// Picks every weight, activation, bias and response randomly from either parent.
+    #[default]
+    Uniform,
+    /// Averages the weights, biases and responses, and picks the activations randomly.
+    Average,
+    /// Cuts the matching links at `points` random places and takes their weights alternately from
+    /// each parent, starting with the fitter one. Nodes are picked like with `Uniform`.
+    Multipoint { points: usize },
+}
+
+impl Net {
+    /// Crosses this network over with `net2`, as in the NEAT paper.
+    ///
+    /// The links are aligned by innovation. Matching links are combined with
+    /// `Conf::get_crossover_method`, and if only one parent has one enabled, it's enabled with
+    /// probability `Conf::link_enabling_in_child_prob`. Disjoint and excess links are inherited from
+    /// the fitter parent, or from both when the fitness is equal, except for the links of the second
+    /// parent that connect nodes the child links already or that would make a feed-forward child
+    /// cyclic.
+    ///
+    /// The child's hidden nodes are the ones that its links connect, matched by id.
+    pub fn crossover(&self, net2: &Self, conf: &dyn Conf, rng: &mut dyn RngCore) -> Self {
+        let (better, worse) = if self.fitness < net2.fitness {
+            (net2, self)
+        } else {
+            (self, net2)
+        };
+        let equal_fitness = self.fitness == net2.fitness;
+        let method = conf.get_crossover_method();
+
+        let mut cuts = Vec::new();
+        if let CrossoverMethod::Multipoint { points } = method {
+            let matching_count = better
+                .links
+                .iter()
+                .filter(|link| worse.find_link_by_innov(link.innov).is_some())
+                .count();
+            if 0 < matching_count {
+                let uniform = Uniform::new(0, matching_count);
+                cuts = (0..points).map(|_| uniform.sample(rng)).collect();
+                cuts.sort_unstable();
+            }
+        }
+
+        let mut links = Vec::with_capacity(better.links.len());
+        let mut worse_only_links = Vec::new();
+        let mut matching_index = 0;
+        let (mut i, mut j) = (0, 0);
+        while i < better.links.len() || j < worse.links.len() {
+            let order = match (better.links.get(i), worse.links.get(j)) {
+                (Some(link), Some(link2)) => link.innov.cmp(&link2.innov),
+                (Some(_), None) => Ordering::Less,
+                (None, _) => Ordering::Greater,
+            };
+            match order {
+                Ordering::Equal => {
+                    let (link, link2) = (&better.links[i], &worse.links[j]);
+                    let mut out_link = link.clone();
+                    match method {
+                        CrossoverMethod::Uniform => {
+                            if Uniform::from(0.0..1.0).sample(rng) < 0.5 {
+                                out_link.weight = link2.weight;
+                            }
+                        }
+                        CrossoverMethod::Average => {
+                            out_link.weight = (link.weight + link2.weight) / 2.0;
+                        }
+                        CrossoverMethod::Multipoint { .. } => {
+                            let cuts_passed = cuts.partition_point(|cut| *cut < matching_index);
+                            if !cuts_passed.is_multiple_of(2) {
+                                out_link.weight = link2.weight;
+                            }
+                        }
+                    }
+                    if link.enabled != link2.enabled {
+                        out_link.enabled = Uniform::from(0.0..1.0).sample(rng)
+                            < conf.link_enabling_in_child_prob();
+                    }
+                    links.push(out_link);
+                    matching_index += 1;
+                    i += 1;
+                    j += 1;
+                }
+                Ordering::Less => {
+                    links.push(better.links[i].clone());
+                    i += 1;
+                }
+                Ordering::Greater => {
+                    if equal_fitness {
+                        worse_only_links.push(worse.links[j].clone());
+                    }
+                    j += 1;
+                }
+            }
+        }
+
+        if !worse_only_links.is_empty() {
+            // The same pair of nodes can be linked under different innovations, such as when
+            // innovations are only shared within a generation.
+            let mut pairs: HashSet<(usize, usize)> =
+                links.iter().map(|link| (link.from, link.to)).collect();
+            let mut successors = HashMap::<usize, Vec<usize>>::new();
+            for link in &links {
+                successors.entry(link.from).or_default().push(link.to);
+            }
+            for link in worse_only_links {
+                if pairs.contains(&(link.from, link.to))
+                    || (!better.recurrent && reaches(&successors, link.to, link.from))
+                {
+                    continue;
+                }
+                pairs.insert((link.from, link.to));
+                successors.entry(link.from).or_default().push(link.to);
+                links.push(link);
+            }
+            links.sort_by_key(|link| link.innov);
+        }
+
+        let mut out = Self {
+            inputs_count: better.inputs_count,
+            outputs_count: better.outputs_count,
+            nodes: Vec::new(),
+            links: Vec::new(),
+            in_species: false,
+            fitness: 0.0,
+            recurrent: better.recurrent,
+            activations: Vec::new(),
+        };
+
+        let fixed_nodes_count = better.fixed_nodes_count();
+        let hidden_ids: BTreeSet<usize> = links
+            .iter()
+            .flat_map(|link| [link.from, link.to])
+            .filter(|id| fixed_nodes_count <= *id)
+            .collect();
+        let ids = (0..fixed_nodes_count).chain(hidden_ids);
+        for (index, id) in ids.enumerate() {
+            let better_node = better.node_index(id).map(|i| &better.nodes[i]);
+            let worse_node = worse.node_index(id).map(|i| &worse.nodes[i]);
+            let mut node = match (better_node, worse_node) {
+                (Some(node), Some(node2)) if out.inputs_count < index => {
+                    combine_nodes(node, node2, method, rng)
+                }
+                (Some(node), _) | (None, Some(node)) => node.clone(),
+                (None, None) => unreachable!("Links connect nodes of their parent."),
+            };
+            node.index = index;
+            node.in_link_indices.clear();
+            out.nodes.push(node);
+        }
+
+        for link in links {
+            let to = out.link_to(&link);
+            out.nodes[to].in_link_indices.push(out.links.len());
+            out.links.push(link);
+        }
+
+        out
+    }
+
+    /// Finds the position of the link with the innovation in `self.links`.
+    fn find_link_by_innov(&self, innov: usize) -> Option<usize> {
+        self.links
+            .binary_search_by_key(&innov, |link| link.innov)
+            .ok()
+    }
+}
+
+/// Combines the attributes of a node that both parents have.
+fn combine_nodes(
+    node: &Node,
+    node2: &Node,
+    method: CrossoverMethod,
+    rng: &mut dyn RngCore,
+) -> Node {
+    let mut out = node.clone();
+    if Uniform::from(0.0..1.0).sample(rng) < 0.5 {
+        out.activation = node2.activation;
+    }
+    if method == CrossoverMethod::Average {
+        out.bias = (node.bias + node2.bias) / 2.0;
+        out.response = (node.response + node2.response) / 2.0;
+    } else {
+        if Uniform::from(0.0..1.0).sample(rng) < 0.5 {
+            out.bias = node2.bias;
+        }
+        if Uniform::from(0.0..1.0).sample(rng) < 0.5 {
+            out.response = node2.response;
+        }
+    }
+    out
+}
+
+/// Checks if there's a path from the node `from` to the node `to`.
+fn reaches(successors: &HashMap<usize, Vec<usize>>, from: usize, to: usize) -> bool {
+    let mut visited = HashSet::from([from]);
+    let mut stack = vec![from];
+    while let Some(id) = stack.pop() {
+        if id == to {
+            return true;
+        }
+        for next in successors.get(&id).into_iter().flatten() {
+            if visited.insert(*next) {
+                stack.push(*next);
+            }
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use rand::SeedableRng;
+
+    use crate::neat::{Conf, CrossoverMethod, InnovationRegistry, NeatRng, Net, TestConf};
+
+    struct MethodConf {
+        method: CrossoverMethod,
+    }
+
+    impl Conf for MethodConf {
+        fn get_crossover_method(&self) -> CrossoverMethod {
+            self.method
+        }
+    }
+
+    /// Two nets that split a link each, in different generations.
+    fn parents(seed: u64) -> (Net, Net) {
+        let conf = TestConf { threads: 1 };
+        let mut innovs = InnovationRegistry::default();
+        let mut rng = NeatRng::seed_from_u64(seed);
+        let mut net = Net::new(3, 2, &mut innovs, &conf, &mut rng);
+        let mut net2 = net.clone();
+        net.mutate_node(&mut innovs, &conf, &mut rng);
+        innovs.start_generation(innovs.scope);
+        net2.mutate_node(&mut innovs, &conf, &mut rng);
+        (net, net2)
+    }
+
+    fn innovs(net: &Net) -> Vec<usize> {
+        net.links.iter().map(|link| link.innov).collect()
+    }
+
+    fn assert_valid(child: &Net) {
+        let pairs: HashSet<(usize, usize)> = child
+            .links
+            .iter()
+            .map(|link| (link.from, link.to))
+            .collect();
+        assert_eq!(pairs.len(), child.links.len());
+        assert!(child.links.is_sorted_by_key(|link| link.innov));
+        assert!(!child.has_cycle());
+    }
+
+    #[test]
+    fn fitter_parent_gives_the_structure() {
+        let conf = TestConf { threads: 1 };
+        let mut rng = NeatRng::seed_from_u64(3);
+        for seed in 0..10 {
+            let (mut net, mut net2) = parents(seed);
+            net.fitness = 1.0;
+            net2.fitness = 2.0;
+            for child in [
+                net.crossover(&net2, &conf, &mut rng),
+                net2.crossover(&net, &conf, &mut rng),
+            ] {
+                assert_valid(&child);
+                assert_eq!(innovs(&child), innovs(&net2));
+                let ids = |net: &Net| net.nodes.iter().map(|node| node.id).collect::<Vec<_>>();
+                assert_eq!(ids(&child), ids(&net2));
+            }
+        }
+    }
+
+    #[test]
+    fn equal_fitness_takes_links_from_both_without_repeating_pairs() {
+        let conf = TestConf { threads: 1 };
+        let mut rng = NeatRng::seed_from_u64(3);
+        let mut repeated_pairs = 0;
+        for seed in 0..10 {
+            let (net, net2) = parents(seed);
+            let child = net.crossover(&net2, &conf, &mut rng);
+            assert_valid(&child);
+
+            let mut expected = innovs(&net);
+            for link in &net2.links {
+                let linked = net
+                    .links
+                    .iter()
+                    .any(|link2| (link2.from, link2.to) == (link.from, link.to));
+                if !linked {
+                    expected.push(link.innov);
+                } else if net.find_link_by_innov(link.innov).is_none() {
+                    repeated_pairs += 1;
+                }
+            }
+            expected.sort_unstable();
+            expected.dedup();
+            assert_eq!(innovs(&child), expected);
+        }
+        // Some parents split the same link, so they link the same pairs under new innovations.
+        assert!(0 < repeated_pairs);
+    }
+
+    #[test]
+    fn multipoint_alternates_the_parents_at_the_cuts() {
+        let mut rng = NeatRng::seed_from_u64(3);
+        for points in 0..3 {
+            let conf = MethodConf {
+                method: CrossoverMethod::Multipoint { points },
+            };
+            for seed in 0..10 {
+                let (mut net, mut net2) = parents(seed);
+                net.fitness = 2.0;
+                for link in &mut net.links {
+                    link.weight = 1.0;
+                }
+                for link in &mut net2.links {
+                    link.weight = -1.0;
+                }
+                let child = net.crossover(&net2, &conf, &mut rng);
+                assert_valid(&child);
+
+                let weights: Vec<f64> = child
+                    .links
+                    .iter()
+                    .filter(|link| net2.find_link_by_innov(link.innov).is_some())
+                    .map(|link| link.weight)
+                    .collect();
+                assert_eq!(weights[0], 1.0);
+                let switches = weights.windows(2).filter(|w| w[0] != w[1]).count();
+                assert!(switches <= points);
+                if points == 0 {
+                    assert!(weights.iter().all(|weight| *weight == 1.0));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use std::fmt::Write;
+
+use crate::neat::{Activation, Net};
+
+/// Options of `Net::to_dot`.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct DotOptions {
+    /// Leaves out the disabled links instead of drawing them dashed.
+    pub hide_disabled_links: bool,
+    /// Leaves out hidden nodes that no drawn link touches.
+    pub hide_dangling_nodes: bool,
+}
+
+impl Net {
+    /// Exports the network as a Graphviz DOT graph.
+    ///
+    /// Inputs are boxes, the bias is a diamond, outputs are double circles and hidden nodes are
+    /// circles labeled with their id. Positive weights are blue, negative weights are red, and the
+    /// width of a link grows with the magnitude of its weight.
+    pub fn to_dot(&self, options: &DotOptions) -> String {
+        let outputs_start = self.inputs_count + 1;
+        let hidden_start = outputs_start + self.outputs_count;
+
+        let links: Vec<_> = self
+            .links
+            .iter()
+            .filter(|link| link.enabled || !options.hide_disabled_links)
+            .collect();
+
+        let mut connected = vec![false; self.nodes.len()];
+        for link in &links {
+            connected[self.link_from(link)] = true;
+            connected[self.link_to(link)] = true;
+        }
+
+        let mut out = String::new();
+        writeln!(out, "digraph net {{").unwrap();
+        writeln!(out, "    rankdir=LR;").unwrap();
+
+        writeln!(out, "    {{").unwrap();
+        writeln!(out, "        rank=source;").unwrap();
+        for i in 0..self.inputs_count {
+            writeln!(
+                out,
+                "        n{} [label=\"in {}\", shape=box, style=filled, fillcolor=lightgray];",
+                i, i
+            )
+            .unwrap();
+        }
+        writeln!(
+            out,
+            "        n{} [label=\"bias\", shape=diamond, style=filled, fillcolor=lightyellow];",
+            self.inputs_count
+        )
+        .unwrap();
+        writeln!(out, "    }}").unwrap();
+
+        writeln!(out, "    {{").unwrap();
+        writeln!(out, "        rank=sink;").unwrap();
+        for i in outputs_start..hidden_start {
+            writeln!(
+                out,
+                "        n{} [label=\"out {}{}\", shape=doublecircle, style=filled, fillcolor=lightblue];",
+                i,
+                i - outputs_start,
+                self.activation_label(i)
+            )
+            .unwrap();
+        }
+        writeln!(out, "    }}").unwrap();
+
+        for (i, is_connected) in connected.iter().enumerate().skip(hidden_start) {
+            if options.hide_dangling_nodes && !is_connected {
+                continue;
+            }
+            writeln!(
+                out,
+                "    n{} [label=\"{}{}\", shape=circle];",
+                i,
+                self.nodes[i].id,
+                self.activation_label(i)
+            )
+            .unwrap();
+        }
+
+        for link in links {
+            let color = if link.weight < 0.0 { "red" } else { "blue" };
+            let penwidth = 0.5 + link.weight.abs().min(5.0);
+            let style = if link.enabled { "solid" } else { "dashed" };
+            writeln!(
+                out,
+                "    n{} -> n{} [label=\"{:.3}\", color={}, penwidth={:.2}, style={}];",
+                self.link_from(link),
+                self.link_to(link),
+                link.weight,
+                color,
+                penwidth,
+                style
+            )
+            .unwrap();
+        }
+
+        writeln!(out, "}}").unwrap();
+        out
+    }
+
+    /// The activation of a node as a label suffix. The default sigmoid isn't shown.
+    fn activation_label(&self, node_index: usize) -> String {
+        match self.nodes[node_index].activation {
+            Activation::Sigmoid => String::new(),
+            activation => format!("\\n{}", activation.name()),
+        }
+    }
+}
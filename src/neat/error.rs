@@ -0,0 +1,104 @@
+use std::{fmt, io};
+
+use crate::neat::{LoadError, NeatPythonError, OnnxError};
+
+/// The errors of this crate.
+///
+/// Unless noted otherwise, a method that returns an error hasn't changed anything, so the caller can
+/// fix the cause and try again.
+#[derive(Debug)]
+pub enum NeatError {
+    /// The network got a different number of inputs than it has. Recoverable.
+    InputLength { expected: usize, actual: usize },
+    /// The fitness of a net is NaN. `Pop::next_gen` checks this before changing the population, so
+    /// setting a valid fitness and calling it again recovers.
+    NanFitness { net: usize },
+    /// The shared fitness of a species sums to zero, a negative number, infinity or NaN, so
+    /// fitness-proportionate selection is impossible. `Species::make_child` and `Pop::next_gen`
+    /// recover by selecting parents uniformly.
+    DegenerateFitness { avarage_fitness: f64 },
+    /// The species has no members. Recoverable.
+    EmptySpecies,
+    /// Every species was removed for being stale or empty. The species of the population have
+    /// already been updated, so the population can't continue and has to be recreated.
+    Extinct,
+    /// A link with this innovation isn't in the node. The genome is corrupt, which isn't recoverable.
+    MissingLink { innov: usize },
+    /// The enabled links form a cycle, so the network can't be compiled. Recurrent networks have to
+    /// be evaluated with `Net::eval`.
+    Cycle,
+    /// The network is recurrent, so `Net::eval` keeps state between calls and a compiled network
+    /// couldn't give the same results, even if the links don't form a cycle.
+    Recurrent,
+    /// A file couldn't be loaded.
+    Load(LoadError),
+    /// A network couldn't be exported to ONNX.
+    Onnx(OnnxError),
+    /// A neat-python genome or config couldn't be converted.
+    NeatPython(NeatPythonError),
+    /// A reporter couldn't write its output.
+    Io(io::Error),
+}
+
+impl fmt::Display for NeatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NeatError::InputLength { expected, actual } => {
+                write!(f, "expected {} inputs, got {}", expected, actual)
+            }
+            NeatError::NanFitness { net } => write!(f, "net {} has NaN fitness", net),
+            NeatError::DegenerateFitness { avarage_fitness } => write!(
+                f,
+                "can't select parents with a shared fitness sum of {}",
+                avarage_fitness
+            ),
+            NeatError::EmptySpecies => write!(f, "the species has no members"),
+            NeatError::Extinct => write!(f, "every species went extinct"),
+            NeatError::MissingLink { innov } => {
+                write!(f, "no link with innovation {} in the node", innov)
+            }
+            NeatError::Cycle => write!(f, "the network contains a cycle"),
+            NeatError::Recurrent => write!(f, "recurrent networks can't be compiled"),
+            NeatError::Load(err) => err.fmt(f),
+            NeatError::Onnx(err) => err.fmt(f),
+            NeatError::NeatPython(err) => err.fmt(f),
+            NeatError::Io(err) => write!(f, "io error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for NeatError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            NeatError::Load(err) => Some(err),
+            NeatError::Onnx(err) => Some(err),
+            NeatError::NeatPython(err) => Some(err),
+            NeatError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<LoadError> for NeatError {
+    fn from(err: LoadError) -> Self {
+        NeatError::Load(err)
+    }
+}
+
+impl From<OnnxError> for NeatError {
+    fn from(err: OnnxError) -> Self {
+        NeatError::Onnx(err)
+    }
+}
+
+impl From<NeatPythonError> for NeatError {
+    fn from(err: NeatPythonError) -> Self {
+        NeatError::NeatPython(err)
+    }
+}
+
+impl From<io::Error> for NeatError {
+    fn from(err: io::Error) -> Self {
+        NeatError::Io(err)
+    }
+}
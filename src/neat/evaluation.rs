@@ -0,0 +1,225 @@
+use std::time::{Duration, Instant};
+
+use crate::neat::{Conf, NeatError, Net, Pop, Reporter};
+
+/// Scores the networks of a population.
+///
+/// Closures of the form `Fn(&mut Net) -> Result<f64, NeatError>` are evaluators too. Evaluators
+/// are shared between threads, so state that changes during evaluation needs a `Mutex` or an
+/// atomic.
+pub trait FitnessEvaluator: Sync {
+    /// Scores a single network. Higher is better.
+    fn evaluate(&self, net: &mut Net) -> Result<f64, NeatError>;
+
+    /// Scores the whole population by setting the fitness of every network.
+    ///
+    /// Competitive tasks, where networks are scored against each other, override this. The default
+    /// scores every network on its own with `FitnessEvaluator::evaluate`, spread over
+    /// `threads_count` threads, see `Conf::get_threads_count`.
+    fn evaluate_population(&self, nets: &mut [Net], threads_count: usize) -> Result<(), NeatError> {
+        evaluate_each(self, nets, threads_count)
+    }
+}
+
+/// Resolves a thread count of `Conf::get_threads_count`, where 0 means every available core.
+pub(super) fn available_threads(threads_count: usize) -> usize {
+    match threads_count {
+        0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    }
+}
+
+/// Scores every network on its own, splitting the networks into one contiguous chunk per thread.
+///
+/// A network's fitness only depends on the network, so the results don't depend on the number of
+/// threads. If several networks fail, the error of the first one is returned.
+fn evaluate_each<E: FitnessEvaluator + ?Sized>(
+    evaluator: &E,
+    nets: &mut [Net],
+    threads_count: usize,
+) -> Result<(), NeatError> {
+    let threads_count = available_threads(threads_count);
+
+    if threads_count == 1 || nets.len() <= 1 {
+        for net in nets {
+            net.fitness = evaluator.evaluate(net)?;
+        }
+        return Ok(());
+    }
+
+    let chunk_size = nets.len().div_ceil(threads_count);
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = nets
+            .chunks_mut(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || -> Result<(), NeatError> {
+                    for net in chunk {
+                        net.fitness = evaluator.evaluate(net)?;
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+
+        // Joining every thread before returning keeps the first error independent of timing.
+        let mut out = Ok(());
+        for handle in handles {
+            let result = handle
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+            if out.is_ok() {
+                out = result;
+            }
+        }
+        out
+    })
+}
+
+impl<F> FitnessEvaluator for F
+where
+    F: Fn(&mut Net) -> Result<f64, NeatError> + Sync,
+{
+    fn evaluate(&self, net: &mut Net) -> Result<f64, NeatError> {
+        self(net)
+    }
+}
+
+/// When `Pop::run` stops. The run stops as soon as any of the set limits is reached, so at least
+/// one of them should be set.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Termination {
+    /// Stops once a network reaches this fitness.
+    pub fitness_threshold: Option<f64>,
+    /// Stops after evaluating this many generations.
+    pub max_generations: Option<u32>,
+    /// Stops after evaluating this many networks. Checked after every generation, so the last
+    /// generation may go over it.
+    pub max_evaluations: Option<u64>,
+    /// Stops once this much time has passed. Checked after every generation.
+    pub time_limit: Option<Duration>,
+}
+
+/// Why `Pop::run` stopped.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StopReason {
+    FitnessThreshold,
+    GenerationCap,
+    EvaluationBudget,
+    TimeLimit,
+}
+
+/// The result of `Pop::run`.
+pub struct RunSummary {
+    /// The fittest network of the run, with its fitness.
+    pub champion: Net,
+    /// The number of generations that were evaluated.
+    pub generations: u32,
+    /// The number of networks that were evaluated.
+    pub evaluations: u64,
+    pub elapsed: Duration,
+    pub stop_reason: StopReason,
+}
+
+impl Termination {
+    /// Checks the limits in the order of the fields.
+    fn check(
+        &self,
+        best_fitness: f64,
+        generations: u32,
+        evaluations: u64,
+        elapsed: Duration,
+    ) -> Option<StopReason> {
+        if self
+            .fitness_threshold
+            .is_some_and(|threshold| threshold <= best_fitness)
+        {
+            Some(StopReason::FitnessThreshold)
+        } else if self.max_generations.is_some_and(|max| max <= generations) {
+            Some(StopReason::GenerationCap)
+        } else if self.max_evaluations.is_some_and(|max| max <= evaluations) {
+            Some(StopReason::EvaluationBudget)
+        } else if self.time_limit.is_some_and(|limit| limit <= elapsed) {
+            Some(StopReason::TimeLimit)
+        } else {
+            None
+        }
+    }
+}
+
+impl Pop {
+    /// Evaluates the population and breeds the next generation until `termination` is reached,
+    /// calling the hooks of `reporters` along the way.
+    ///
+    /// The population is left at the last evaluated generation, so `pop.nets` still holds the
+    /// fitness that `evaluator` gave them.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of `evaluator` and `Pop::next_gen`, `NeatError::Io` if a reporter fails,
+    /// and `NeatError::Extinct` if the population is empty.
+    pub fn run(
+        &mut self,
+        evaluator: &dyn FitnessEvaluator,
+        termination: &Termination,
+        reporters: &mut [&mut dyn Reporter],
+        conf: &dyn Conf,
+    ) -> Result<RunSummary, NeatError> {
+        let start = Instant::now();
+        let mut champion: Option<Net> = None;
+        let mut generations = 0;
+        let mut evaluations = 0;
+
+        loop {
+            for reporter in reporters.iter_mut() {
+                reporter.generation_start(self)?;
+            }
+
+            evaluator.evaluate_population(&mut self.nets, conf.get_threads_count())?;
+            generations += 1;
+            evaluations += self.nets.len() as u64;
+
+            let best = self
+                .nets
+                .iter()
+                .filter(|net| !net.fitness.is_nan())
+                .max_by(|a, b| a.fitness.partial_cmp(&b.fitness).unwrap())
+                .or_else(|| self.nets.first())
+                .ok_or(NeatError::Extinct)?;
+            if champion
+                .as_ref()
+                .is_none_or(|champion| champion.fitness < best.fitness)
+            {
+                for reporter in reporters.iter_mut() {
+                    reporter.new_best(self.generation, best)?;
+                }
+                champion = Some(best.clone());
+            }
+            let best_fitness = champion.as_ref().unwrap().fitness;
+
+            let stats = self.stats();
+            for reporter in reporters.iter_mut() {
+                reporter.generation_end(self, &stats)?;
+            }
+
+            let elapsed = start.elapsed();
+            if let Some(stop_reason) =
+                termination.check(best_fitness, generations, evaluations, elapsed)
+            {
+                return Ok(RunSummary {
+                    champion: champion.unwrap(),
+                    generations,
+                    evaluations,
+                    elapsed,
+                    stop_reason,
+                });
+            }
+
+            let generation = self.generation;
+            for species in self.breed(conf)? {
+                for reporter in reporters.iter_mut() {
+                    reporter.species_extinct(generation, &species)?;
+                }
+            }
+        }
+    }
+}
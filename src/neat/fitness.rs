@@ -0,0 +1,157 @@
+/// Maps the raw fitness of the population to the positive values that fitness sharing and
+/// fitness-proportionate selection need, see `Conf::get_fitness_transform`.
+///
+/// Only the shared fitness is transformed. Staleness, culling, elitism and the statistics use the
+/// raw fitness.
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub enum FitnessTransform {
+    /// Uses the raw fitness. Species with negative avarage fitness then get no share of the
+    /// offspring, and infinite fitness splits the offspring evenly between the species.
+    #[default]
+    Identity,
+    /// Subtracts the lowest fitness of the population, so the worst net gets 0. Infinite fitness
+    /// counts as the lowest or the highest finite fitness.
+    ShiftToMin,
+    /// Replaces the fitness by its rank divided by the population size, from `1/n` for the worst
+    /// net to 1 for the best. Equal fitness gets the average rank. Works for any fitness, even
+    /// infinite.
+    Rank,
+    /// `exp((fitness - max) / temperature)`, from 1 for the best net down towards 0. A lower
+    /// temperature selects more strongly. `max` is the highest finite fitness, and infinite fitness
+    /// gets 1 or 0.
+    Exponential { temperature: f64 },
+}
+
+impl FitnessTransform {
+    /// Transforms the fitness of every net.
+    pub fn apply(&self, fitnesses: &[f64]) -> Vec<f64> {
+        match self {
+            FitnessTransform::Identity => fitnesses.to_vec(),
+            FitnessTransform::ShiftToMin => {
+                let (min, max) = finite_range(fitnesses);
+                fitnesses
+                    .iter()
+                    .map(|fitness| fitness.max(min).min(max) - min)
+                    .collect()
+            }
+            FitnessTransform::Rank => {
+                let mut order: Vec<usize> = (0..fitnesses.len()).collect();
+                order.sort_by(|a, b| fitnesses[*a].total_cmp(&fitnesses[*b]));
+
+                let len = fitnesses.len() as f64;
+                let mut out = vec![0.0; fitnesses.len()];
+                let mut start = 0;
+                while start < order.len() {
+                    let mut end = start + 1;
+                    while end < order.len() && fitnesses[order[end]] == fitnesses[order[start]] {
+                        end += 1;
+                    }
+                    // Ranks start + 1 ..= end, averaged over the tie.
+                    let rank = ((start + 1 + end) as f64) / 2.0;
+                    for i in &order[start..end] {
+                        out[*i] = rank / len;
+                    }
+                    start = end;
+                }
+                out
+            }
+            FitnessTransform::Exponential { temperature } => {
+                let (_, max) = finite_range(fitnesses);
+                fitnesses
+                    .iter()
+                    .map(|fitness| ((fitness.min(max) - max) / temperature).exp())
+                    .collect()
+            }
+        }
+    }
+}
+
+/// The lowest and the highest finite fitness, or zeros if there's none.
+fn finite_range(fitnesses: &[f64]) -> (f64, f64) {
+    let finite = fitnesses
+        .iter()
+        .copied()
+        .filter(|fitness| fitness.is_finite());
+    let min = finite.clone().fold(f64::INFINITY, f64::min);
+    let max = finite.fold(f64::NEG_INFINITY, f64::max);
+    if min <= max {
+        (min, max)
+    } else {
+        (0.0, 0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::neat::{Conf, FitnessTransform, Pop};
+
+    struct TransformConf {
+        transform: FitnessTransform,
+    }
+
+    impl Conf for TransformConf {
+        fn get_fitness_transform(&self) -> FitnessTransform {
+            self.transform
+        }
+    }
+
+    #[test]
+    fn negative_and_unbounded_fitness_breeds_with_every_transform() {
+        let transforms = [
+            FitnessTransform::Identity,
+            FitnessTransform::ShiftToMin,
+            FitnessTransform::Rank,
+            FitnessTransform::Exponential { temperature: 1.0 },
+        ];
+        for transform in transforms {
+            let conf = TransformConf { transform };
+            let mut pop = Pop::with_seed(40, 2, 1, 5, &conf);
+            for generation in 0..6 {
+                for (i, net) in pop.nets.iter_mut().enumerate() {
+                    net.fitness = match (generation + i) % 7 {
+                        0 if 2 < generation => f64::INFINITY,
+                        1 if 2 < generation => f64::NEG_INFINITY,
+                        rest => -1000.0 * (rest as f64),
+                    };
+                }
+                pop.next_gen(&conf).unwrap();
+                assert_eq!(pop.nets.len(), 40);
+            }
+        }
+    }
+
+    #[test]
+    fn infinite_fitness_goes_to_the_ends_of_the_range() {
+        let fitnesses = [-3.0, f64::INFINITY, 1.0, f64::NEG_INFINITY, -1.0];
+
+        let shifted = FitnessTransform::ShiftToMin.apply(&fitnesses);
+        assert_eq!(shifted, [0.0, 4.0, 4.0, 0.0, 2.0]);
+
+        let exponential = FitnessTransform::Exponential { temperature: 2.0 }.apply(&fitnesses);
+        let expected = [(-2.0f64).exp(), 1.0, 1.0, 0.0, (-1.0f64).exp()];
+        for (value, expected) in exponential.iter().zip(&expected) {
+            assert!(
+                (value - expected).abs() < 1e-12,
+                "{} != {}",
+                value,
+                expected
+            );
+        }
+
+        let ranked = FitnessTransform::Rank.apply(&fitnesses);
+        assert_eq!(ranked, [0.4, 1.0, 0.8, 0.2, 0.6]);
+    }
+
+    #[test]
+    fn only_infinite_fitness_stays_finite() {
+        let fitnesses = [f64::INFINITY, f64::NEG_INFINITY, f64::INFINITY];
+        assert_eq!(
+            FitnessTransform::ShiftToMin.apply(&fitnesses),
+            [0.0, 0.0, 0.0]
+        );
+        assert_eq!(
+            FitnessTransform::Exponential { temperature: 1.0 }.apply(&fitnesses),
+            [1.0, 0.0, 1.0]
+        );
+    }
+}
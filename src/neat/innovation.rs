@@ -0,0 +1,347 @@
+use std::{collections::HashMap, ops::Index};
+
+use serde::{Deserialize, Serialize};
+
+/// The innovation object. `from` and `to` are node ids, see `Node::id`.
+#[derive(Serialize, Deserialize)]
+pub struct Innov {
+    pub from: usize,
+    pub to: usize,
+    pub number: usize,
+}
+
+impl Clone for Innov {
+    fn clone(&self) -> Self {
+        Self {
+            from: self.from,
+            to: self.to,
+            number: self.number,
+        }
+    }
+}
+
+/// Which earlier innovations a new link reuses when it connects the same nodes, see
+/// `Conf::get_innovation_scope`.
+#[derive(Clone, Copy, PartialEq, Default, Debug, Serialize, Deserialize)]
+pub enum InnovationScope {
+    /// Only the innovations of the generation being bred.
+    #[default]
+    Generation,
+    /// The innovations of the last `generations` generations, including the one being bred.
+    Window { generations: u32 },
+    /// Every innovation that some net still uses.
+    Global,
+}
+
+/// Numbers the links by the nodes they connect.
+///
+/// Numbers are never reused, and `InnovationRegistry::retain` drops innovations without
+/// renumbering the rest, so the stored numbers can have gaps.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(from = "RegistryData", into = "RegistryData")]
+pub struct InnovationRegistry {
+    /// Sorted by number.
+    innovs: Vec<Innov>,
+    /// The generation that created every innovation of `innovs`.
+    generations: Vec<u32>,
+    /// The newest stored innovation of every pair of nodes, with its generation.
+    lookup: HashMap<(usize, usize), (usize, u32)>,
+    next_number: usize,
+    generation: u32,
+    generation_start: usize,
+    pub scope: InnovationScope,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RegistryData {
+    innovs: Vec<Innov>,
+    generations: Vec<u32>,
+    next_number: usize,
+    generation: u32,
+    generation_start: usize,
+    scope: InnovationScope,
+}
+
+impl InnovationRegistry {
+    pub fn new(scope: InnovationScope) -> Self {
+        Self {
+            innovs: Vec::new(),
+            generations: Vec::new(),
+            lookup: HashMap::new(),
+            next_number: 0,
+            generation: 0,
+            generation_start: 0,
+            scope,
+        }
+    }
+
+    /// The number of stored innovations.
+    pub fn len(&self) -> usize {
+        self.innovs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.innovs.is_empty()
+    }
+
+    /// The stored innovations, sorted by number.
+    pub fn iter(&self) -> impl Iterator<Item = &Innov> {
+        self.innovs.iter()
+    }
+
+    /// Finds an innovation by its number.
+    pub fn get(&self, number: usize) -> Option<&Innov> {
+        self.innovs
+            .binary_search_by_key(&number, |innov| innov.number)
+            .ok()
+            .map(|i| &self.innovs[i])
+    }
+
+    /// The number that the next new innovation gets.
+    pub fn next_number(&self) -> usize {
+        self.next_number
+    }
+
+    /// The number of generations started so far.
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    /// The number of innovation numbers taken since the current generation started.
+    pub fn generation_innovations_count(&self) -> usize {
+        self.next_number - self.generation_start
+    }
+
+    /// Starts a generation. The innovations from now on are the new ones of the generation.
+    pub fn start_generation(&mut self, scope: InnovationScope) {
+        self.generation += 1;
+        self.generation_start = self.next_number;
+        self.scope = scope;
+    }
+
+    /// The number of the innovation that connects the node `from` to the node `to`. An innovation
+    /// within `InnovationRegistry::scope` is reused, otherwise a new one is added.
+    pub fn get_or_insert(&mut self, from: usize, to: usize) -> usize {
+        if let Some((number, generation)) = self.lookup.get(&(from, to)) {
+            let in_scope = match self.scope {
+                InnovationScope::Generation => *generation == self.generation,
+                InnovationScope::Window { generations } => {
+                    self.generation - generation < generations
+                }
+                InnovationScope::Global => true,
+            };
+            if in_scope {
+                return *number;
+            }
+        }
+
+        let number = self.reserve();
+        self.innovs.push(Innov { from, to, number });
+        self.generations.push(self.generation);
+        self.lookup.insert((from, to), (number, self.generation));
+        number
+    }
+
+    /// Takes a number without adding an innovation, for nodes that don't split a link.
+    pub fn reserve(&mut self) -> usize {
+        self.next_number += 1;
+        self.next_number - 1
+    }
+
+    /// The innovations numbered from `start` on.
+    pub(super) fn since(&self, start: usize) -> &[Innov] {
+        &self.innovs[self.innovs.partition_point(|innov| innov.number < start)..]
+    }
+
+    /// Keeps only the innovations for which `keep` returns `true`, such as the ones that some net
+    /// still uses. The others can't be reused anymore, and their numbers aren't given out again.
+    pub fn retain(&mut self, mut keep: impl FnMut(&Innov) -> bool) {
+        let mut generations = std::mem::take(&mut self.generations).into_iter();
+        let mut kept_generations = Vec::with_capacity(self.innovs.len());
+        self.innovs.retain(|innov| {
+            let generation = generations.next().unwrap();
+            let kept = keep(innov);
+            if kept {
+                kept_generations.push(generation);
+            }
+            kept
+        });
+        self.generations = kept_generations;
+        self.rebuild_lookup();
+    }
+
+    fn rebuild_lookup(&mut self) {
+        self.lookup.clear();
+        for (innov, generation) in self.innovs.iter().zip(&self.generations) {
+            self.lookup
+                .insert((innov.from, innov.to), (innov.number, *generation));
+        }
+    }
+
+    /// Checks that the innovations are sorted by number and below `next_number`, and that the
+    /// generations and counters are consistent. Returns the number of the bad innovation otherwise.
+    pub(super) fn validate(&self) -> Result<(), usize> {
+        for (i, innov) in self.innovs.iter().enumerate() {
+            let sorted = i == 0 || self.innovs[i - 1].number < innov.number;
+            if !sorted || self.next_number <= innov.number {
+                return Err(innov.number);
+            }
+        }
+        if self.generations.len() != self.innovs.len()
+            || self
+                .generations
+                .iter()
+                .any(|generation| self.generation < *generation)
+            || self.next_number < self.generation_start
+        {
+            return Err(self.generation_start);
+        }
+        Ok(())
+    }
+}
+
+impl Default for InnovationRegistry {
+    fn default() -> Self {
+        Self::new(InnovationScope::default())
+    }
+}
+
+impl Index<usize> for InnovationRegistry {
+    type Output = Innov;
+
+    fn index(&self, number: usize) -> &Innov {
+        self.get(number)
+            .expect("Links only use innovations of the registry.")
+    }
+}
+
+impl From<RegistryData> for InnovationRegistry {
+    fn from(data: RegistryData) -> Self {
+        let mut out = Self {
+            innovs: data.innovs,
+            generations: data.generations,
+            lookup: HashMap::new(),
+            next_number: data.next_number,
+            generation: data.generation,
+            generation_start: data.generation_start,
+            scope: data.scope,
+        };
+        out.rebuild_lookup();
+        out
+    }
+}
+
+impl From<InnovationRegistry> for RegistryData {
+    fn from(registry: InnovationRegistry) -> Self {
+        Self {
+            innovs: registry.innovs,
+            generations: registry.generations,
+            next_number: registry.next_number,
+            generation: registry.generation,
+            generation_start: registry.generation_start,
+            scope: registry.scope,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::neat::{InnovationRegistry, InnovationScope};
+
+    #[test]
+    fn generation_scope_reuses_only_this_generation() {
+        let mut innovs = InnovationRegistry::new(InnovationScope::Generation);
+        assert_eq!(innovs.get_or_insert(0, 3), 0);
+        assert_eq!(innovs.get_or_insert(1, 3), 1);
+        assert_eq!(innovs.get_or_insert(0, 3), 0);
+
+        innovs.start_generation(InnovationScope::Generation);
+        assert_eq!(innovs.get_or_insert(0, 3), 2);
+        assert_eq!(innovs.get_or_insert(0, 3), 2);
+        assert_eq!(innovs.generation_innovations_count(), 1);
+    }
+
+    #[test]
+    fn window_scope_reuses_the_last_generations() {
+        let scope = InnovationScope::Window { generations: 2 };
+        let mut innovs = InnovationRegistry::new(scope);
+        assert_eq!(innovs.get_or_insert(0, 3), 0);
+
+        innovs.start_generation(scope);
+        assert_eq!(innovs.get_or_insert(0, 3), 0);
+        assert_eq!(innovs.get_or_insert(1, 3), 1);
+
+        // Generation 0 left the window, generation 1 is still in it.
+        innovs.start_generation(scope);
+        assert_eq!(innovs.get_or_insert(0, 3), 2);
+        assert_eq!(innovs.get_or_insert(1, 3), 1);
+
+        // The newest innovation of a pair is the one reused.
+        innovs.start_generation(scope);
+        assert_eq!(innovs.get_or_insert(0, 3), 2);
+        assert_eq!(innovs.get_or_insert(1, 3), 3);
+    }
+
+    #[test]
+    fn global_scope_reuses_every_kept_innovation() {
+        let mut innovs = InnovationRegistry::new(InnovationScope::Global);
+        assert_eq!(innovs.get_or_insert(0, 3), 0);
+        for _ in 0..10 {
+            innovs.start_generation(InnovationScope::Global);
+        }
+        assert_eq!(innovs.get_or_insert(0, 3), 0);
+        assert_eq!(innovs.len(), 1);
+    }
+
+    #[test]
+    fn retain_never_gives_numbers_out_again() {
+        let mut innovs = InnovationRegistry::new(InnovationScope::Global);
+        innovs.get_or_insert(0, 3);
+        innovs.get_or_insert(1, 3);
+        innovs.reserve();
+        innovs.get_or_insert(2, 3);
+
+        innovs.retain(|innov| innov.number == 1);
+        assert_eq!(
+            innovs.iter().map(|innov| innov.number).collect::<Vec<_>>(),
+            [1]
+        );
+        assert!(innovs.get(0).is_none());
+        assert!(innovs.validate().is_ok());
+
+        assert_eq!(innovs.get_or_insert(1, 3), 1);
+        // The dropped innovations can't be reused, and their numbers are gone for good.
+        assert_eq!(innovs.get_or_insert(0, 3), 4);
+        assert_eq!(innovs.get_or_insert(2, 3), 5);
+        assert_eq!(innovs.next_number(), 6);
+    }
+
+    #[test]
+    fn deserializing_rebuilds_the_lookup() {
+        let scope = InnovationScope::Window { generations: 2 };
+        let mut innovs = InnovationRegistry::new(scope);
+        innovs.get_or_insert(0, 3);
+        innovs.start_generation(scope);
+        innovs.get_or_insert(1, 3);
+        innovs.start_generation(scope);
+        innovs.get_or_insert(2, 3);
+        innovs.retain(|innov| innov.number != 2);
+
+        let json = serde_json::to_string(&innovs).unwrap();
+        let bytes = bincode::serialize(&innovs).unwrap();
+        let loaded: [InnovationRegistry; 2] = [
+            serde_json::from_str(&json).unwrap(),
+            bincode::deserialize(&bytes).unwrap(),
+        ];
+        for mut loaded in loaded {
+            assert!(loaded.validate().is_ok());
+            assert_eq!(serde_json::to_string(&loaded).unwrap(), json);
+            assert_eq!(loaded.generation(), 2);
+            assert_eq!(loaded.generation_innovations_count(), 1);
+            // (0, 3) is out of the window and (2, 3) was dropped, (1, 3) is reused.
+            assert_eq!(loaded.get_or_insert(1, 3), 1);
+            assert_eq!(loaded.get_or_insert(0, 3), 3);
+            assert_eq!(loaded.get_or_insert(2, 3), 4);
+        }
+    }
+}
@@ -1,16 +1,50 @@
+mod activation;
+mod checkpoint;
 mod conf;
+mod crossover;
+mod dot;
+mod error;
+mod evaluation;
+mod fitness;
+mod innovation;
+mod neat_python;
 mod neuralnet;
+mod onnx;
+mod phenotype;
 mod population;
+mod reporter;
+mod selection;
+mod serialization;
 mod species;
+mod stats;
 
+pub use activation::{Activation, CustomActivation};
+pub use checkpoint::Checkpointer;
 pub use conf::Conf;
-pub use neuralnet::{Innov, Net};
-pub use population::Pop;
+pub use crossover::CrossoverMethod;
+pub use dot::DotOptions;
+pub use error::NeatError;
+pub use evaluation::{FitnessEvaluator, RunSummary, StopReason, Termination};
+pub use fitness::FitnessTransform;
+pub use innovation::{Innov, InnovationRegistry, InnovationScope};
+pub use neat_python::{
+    NeatPythonConf, NeatPythonConnection, NeatPythonError, NeatPythonGenome, NeatPythonNode,
+};
+pub use neuralnet::Net;
+pub use onnx::OnnxError;
+pub use phenotype::{CompiledLink, CompiledNet, CompiledNode};
+pub use population::{NeatRng, Pop};
+pub use reporter::{CsvReporter, JsonLinesReporter, Reporter, StdoutReporter};
+pub use selection::{
+    Rank, Roulette, SelectionStrategy, StochasticUniversalSampling, Tournament, Truncation,
+};
+pub use serialization::{Archive, Format, LoadError, FORMAT_VERSION};
 pub use species::Species;
+pub use stats::{GenerationStats, SizeStats, SpeciesStats};
 
-struct cfg;
+struct Cfg;
 
-impl Conf for cfg {
+impl Conf for Cfg {
     fn get_cull_survival_percentage(&self) -> f64 {
         0.8
     }
@@ -25,9 +59,31 @@ impl Conf for cfg {
     }
 }
 
+/// The configuration of the tests. Nets grow faster than with the defaults, so a few generations
+/// already exercise hidden nodes and new innovations.
+#[cfg(test)]
+pub(crate) struct TestConf {
+    pub threads: usize,
+}
+
+#[cfg(test)]
+impl Conf for TestConf {
+    fn get_link_addition_mutation_prob(&self) -> f64 {
+        0.5
+    }
+    fn get_node_addition_mutation_prob(&self) -> f64 {
+        0.2
+    }
+    fn get_threads_count(&self) -> usize {
+        self.threads
+    }
+}
+
 pub fn tst() {
-    let mut innovs = Vec::<Innov>::new();
-    let conf = cfg {};
-    let mut net = Net::new(2, 1, &mut innovs, 0, &conf);
-    net.add_link(&mut innovs, 0, 1.0, 0, 1);
+    use rand::SeedableRng;
+
+    let mut innovs = InnovationRegistry::default();
+    let conf = Cfg {};
+    let mut net = Net::new(2, 1, &mut innovs, &conf, &mut NeatRng::seed_from_u64(0));
+    net.add_link(&mut innovs, 1.0, 0, 1);
 }
@@ -0,0 +1,884 @@
+use std::{collections::HashMap, fmt};
+
+use rand::prelude::*;
+use rand_distr::Normal;
+use serde::{Deserialize, Serialize};
+
+use crate::neat::{
+    Activation, Conf, FitnessTransform, InnovationRegistry, Net, SelectionStrategy, Truncation,
+};
+
+use super::neuralnet::Node;
+
+/// The error returned when converting from or to neat-python fails.
+#[derive(Debug)]
+pub enum NeatPythonError {
+    /// The genome JSON is malformed.
+    Syntax(String),
+    /// An activation function that neat-python and this crate don't share.
+    UnsupportedActivation(String),
+    /// Only the `sum` aggregation is supported.
+    UnsupportedAggregation(String),
+    /// A connection uses a node that isn't in the genome.
+    UnknownNode(i64),
+    /// A config line that isn't a section, a `key = value` pair or a comment.
+    ConfigLine(usize),
+    /// A config value that can't be parsed.
+    ConfigValue {
+        section: String,
+        key: String,
+        value: String,
+    },
+}
+
+impl fmt::Display for NeatPythonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NeatPythonError::Syntax(msg) => write!(f, "syntax error: {}", msg),
+            NeatPythonError::UnsupportedActivation(name) => {
+                write!(f, "unsupported activation `{}`", name)
+            }
+            NeatPythonError::UnsupportedAggregation(name) => {
+                write!(f, "unsupported aggregation `{}`", name)
+            }
+            NeatPythonError::UnknownNode(key) => write!(f, "unknown node {}", key),
+            NeatPythonError::ConfigLine(line) => write!(f, "can't parse config line {}", line),
+            NeatPythonError::ConfigValue {
+                section,
+                key,
+                value,
+            } => write!(f, "bad value `{}` for [{}] {}", value, section, key),
+        }
+    }
+}
+
+impl std::error::Error for NeatPythonError {}
+
+/// A node gene of a neat-python `DefaultGenome`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NeatPythonNode {
+    pub key: i64,
+    pub bias: f64,
+    pub response: f64,
+    pub activation: String,
+    pub aggregation: String,
+}
+
+/// A connection gene of a neat-python `DefaultGenome`. The key is `(in, out)`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NeatPythonConnection {
+    pub key: (i64, i64),
+    pub weight: f64,
+    pub enabled: bool,
+}
+
+/// A neat-python `DefaultGenome`.
+///
+/// neat-python has no file format for genomes, so they are exchanged as JSON. On the Python side
+/// a genome can be dumped with:
+///
+/// ```python
+/// json.dump({
+///     "key": g.key,
+///     "fitness": g.fitness,
+///     "nodes": [{"key": n.key, "bias": n.bias, "response": n.response,
+///                "activation": n.activation, "aggregation": n.aggregation}
+///               for n in g.nodes.values()],
+///     "connections": [{"key": list(c.key), "weight": c.weight, "enabled": c.enabled}
+///                     for c in g.connections.values()],
+/// }, file)
+/// ```
+///
+/// Input keys are `-1, -2, ...`, output keys are `0, 1, ...` and hidden keys follow. neat-python
+/// scales the inputs of its activation functions (`sigmoid(5z)`, `tanh(2.5z)`, `sin(5z)`,
+/// `gauss` as `exp(-5z^2)`), so biases and responses are rescaled on conversion to keep the
+/// networks equivalent.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NeatPythonGenome {
+    pub key: i64,
+    pub fitness: Option<f64>,
+    pub nodes: Vec<NeatPythonNode>,
+    pub connections: Vec<NeatPythonConnection>,
+}
+
+/// The factor `c` for which this crate's `activation(x)` equals neat-python's `activation(c * x)`.
+fn activation_scale(activation: Activation) -> Result<f64, NeatPythonError> {
+    match activation {
+        Activation::Sigmoid => Ok(4.9 / 5.0),
+        Activation::Tanh => Ok(1.0 / 2.5),
+        Activation::Sine => Ok(1.0 / 5.0),
+        Activation::Gaussian => Ok(1.0 / 5f64.sqrt()),
+        Activation::Relu | Activation::Identity | Activation::Abs => Ok(1.0),
+        Activation::Step | Activation::Custom(_) => Err(NeatPythonError::UnsupportedActivation(
+            activation.name().to_string(),
+        )),
+    }
+}
+
+impl NeatPythonGenome {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Genomes are always serializable.")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, NeatPythonError> {
+        serde_json::from_str(json).map_err(|err| NeatPythonError::Syntax(err.to_string()))
+    }
+}
+
+impl Net {
+    /// Converts the network to a neat-python genome.
+    ///
+    /// neat-python has no bias node, so the enabled links of the bias node are folded into the
+    /// biases of the nodes they lead to. Disabled bias links are dropped.
+    pub fn to_neat_python(&self, key: i64) -> Result<NeatPythonGenome, NeatPythonError> {
+        let outputs_start = self.inputs_count + 1;
+        let node_key = |index: usize| -> i64 {
+            if index < self.inputs_count {
+                -(index as i64) - 1
+            } else {
+                (index - outputs_start) as i64
+            }
+        };
+
+        let mut nodes = Vec::with_capacity(self.nodes.len() - outputs_start);
+        for node in &self.nodes[outputs_start..] {
+            let mut bias = node.bias;
+            for link_index in &node.in_link_indices {
+                let link = &self.links[*link_index];
+                if link.enabled && self.link_from(link) == self.inputs_count {
+                    bias += node.response * link.weight;
+                }
+            }
+
+            let scale = activation_scale(node.activation)?;
+            nodes.push(NeatPythonNode {
+                key: node_key(node.index),
+                bias: scale * bias,
+                response: scale * node.response,
+                activation: node.activation.name().to_string(),
+                aggregation: "sum".to_string(),
+            });
+        }
+
+        let mut connections = Vec::with_capacity(self.links.len());
+        for link in &self.links {
+            let (from, to) = (self.link_from(link), self.link_to(link));
+            if from == self.inputs_count || to <= self.inputs_count {
+                continue;
+            }
+            connections.push(NeatPythonConnection {
+                key: (node_key(from), node_key(to)),
+                weight: link.weight,
+                enabled: link.enabled,
+            });
+        }
+
+        Ok(NeatPythonGenome {
+            key,
+            fitness: Some(self.fitness),
+            nodes,
+            connections,
+        })
+    }
+
+    /// Builds a network from a neat-python genome. The links get innovations from `innovs`, reusing
+    /// the ones in its scope that connect the same nodes.
+    ///
+    /// neat-python nodes don't come from split innovations, so every hidden node gets its id from a
+    /// reserved innovation number, and doesn't match the hidden nodes of other nets.
+    pub fn from_neat_python(
+        genome: &NeatPythonGenome,
+        inputs_count: usize,
+        outputs_count: usize,
+        innovs: &mut InnovationRegistry,
+        conf: &dyn Conf,
+    ) -> Result<Net, NeatPythonError> {
+        let outputs_start = inputs_count + 1;
+        let hidden_start = outputs_start + outputs_count;
+
+        let mut hidden_keys: Vec<i64> = genome
+            .nodes
+            .iter()
+            .map(|node| node.key)
+            .filter(|key| outputs_count as i64 <= *key)
+            .collect();
+        hidden_keys.sort_unstable();
+        let hidden_indices: HashMap<i64, usize> = hidden_keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| (*key, hidden_start + i))
+            .collect();
+        let node_index = |key: i64| -> Result<usize, NeatPythonError> {
+            if key < 0 && -key <= inputs_count as i64 {
+                Ok((-key - 1) as usize)
+            } else if 0 <= key && key < outputs_count as i64 {
+                Ok(outputs_start + key as usize)
+            } else {
+                hidden_indices
+                    .get(&key)
+                    .copied()
+                    .ok_or(NeatPythonError::UnknownNode(key))
+            }
+        };
+
+        let mut out = Net {
+            inputs_count,
+            outputs_count,
+            nodes: Vec::with_capacity(hidden_start + hidden_keys.len()),
+            links: Vec::with_capacity(genome.connections.len()),
+            in_species: false,
+            fitness: genome.fitness.unwrap_or(0.0),
+            recurrent: conf.allow_recurrent_links(),
+            activations: Vec::new(),
+        };
+        for i in 0..(hidden_start + hidden_keys.len()) {
+            let id = if i < hidden_start {
+                i
+            } else {
+                out.split_node_id(innovs.reserve())
+            };
+            out.nodes.push(Node {
+                in_link_indices: Vec::new(),
+                index: i,
+                id,
+                activation: if i < outputs_start {
+                    Activation::Identity
+                } else {
+                    conf.get_default_activation()
+                },
+                bias: 0.0,
+                response: 1.0,
+            });
+        }
+
+        for node in &genome.nodes {
+            if node.aggregation != "sum" {
+                return Err(NeatPythonError::UnsupportedAggregation(
+                    node.aggregation.clone(),
+                ));
+            }
+            let activation = Activation::from_name(&node.activation, &[])
+                .ok_or_else(|| NeatPythonError::UnsupportedActivation(node.activation.clone()))?;
+            let scale = activation_scale(activation)?;
+
+            let index = node_index(node.key)?;
+            if index < outputs_start {
+                return Err(NeatPythonError::UnknownNode(node.key));
+            }
+            let out_node = &mut out.nodes[index];
+            out_node.activation = activation;
+            out_node.bias = node.bias / scale;
+            out_node.response = node.response / scale;
+        }
+
+        let mut connections: Vec<_> = genome.connections.iter().collect();
+        connections.sort_by_key(|connection| connection.key);
+        for connection in connections {
+            let from = node_index(connection.key.0)?;
+            let to = node_index(connection.key.1)?;
+            if to < outputs_start {
+                return Err(NeatPythonError::UnknownNode(connection.key.1));
+            }
+            out.add_link(innovs, connection.weight, from, to);
+            out.links.last_mut().unwrap().enabled = connection.enabled;
+        }
+        // Reused innovations can be older than the ones before them.
+        out.sort_links();
+
+        Ok(out)
+    }
+}
+
+/// A `Conf` read from a neat-python INI config file.
+///
+/// The fields map to the closest equivalent of this crate. Probabilities that neat-python applies
+/// per gene are applied per genome here, and both disjoint and excess genes use
+/// `compatibility_disjoint_coefficient`.
+#[derive(Clone, Debug)]
+pub struct NeatPythonConf {
+    pub pop_size: usize,
+    pub fitness_threshold: f64,
+    pub num_inputs: usize,
+    pub num_outputs: usize,
+    pub feed_forward: bool,
+
+    pub activation_default: Activation,
+    pub activation_options: Vec<Activation>,
+    pub activation_mutate_rate: f64,
+
+    pub bias_init_mean: f64,
+    pub bias_init_stdev: f64,
+    pub bias_min_value: f64,
+    pub bias_max_value: f64,
+    pub bias_mutate_power: f64,
+    pub bias_mutate_rate: f64,
+
+    pub response_init_mean: f64,
+    pub response_init_stdev: f64,
+    pub response_min_value: f64,
+    pub response_max_value: f64,
+    pub response_mutate_power: f64,
+    pub response_mutate_rate: f64,
+
+    pub weight_init_mean: f64,
+    pub weight_init_stdev: f64,
+    pub weight_min_value: f64,
+    pub weight_max_value: f64,
+    pub weight_mutate_power: f64,
+    pub weight_mutate_rate: f64,
+    pub weight_replace_rate: f64,
+
+    pub compatibility_disjoint_coefficient: f64,
+    pub compatibility_weight_coefficient: f64,
+    pub compatibility_threshold: f64,
+
+    pub conn_add_prob: f64,
+    pub node_add_prob: f64,
+    pub enabled_mutate_rate: f64,
+
+    pub max_stagnation: u32,
+    /// Any positive value keeps the champion and protects its species, see `Conf::keep_champion`.
+    pub species_elitism: usize,
+    pub elitism: usize,
+    pub survival_threshold: f64,
+}
+
+impl Default for NeatPythonConf {
+    /// The defaults of neat-python's example configs.
+    fn default() -> Self {
+        Self {
+            pop_size: 150,
+            fitness_threshold: f64::INFINITY,
+            num_inputs: 2,
+            num_outputs: 1,
+            feed_forward: true,
+
+            activation_default: Activation::Sigmoid,
+            activation_options: vec![Activation::Sigmoid],
+            activation_mutate_rate: 0.0,
+
+            bias_init_mean: 0.0,
+            bias_init_stdev: 1.0,
+            bias_min_value: -30.0,
+            bias_max_value: 30.0,
+            bias_mutate_power: 0.5,
+            bias_mutate_rate: 0.7,
+
+            response_init_mean: 1.0,
+            response_init_stdev: 0.0,
+            response_min_value: -30.0,
+            response_max_value: 30.0,
+            response_mutate_power: 0.0,
+            response_mutate_rate: 0.0,
+
+            weight_init_mean: 0.0,
+            weight_init_stdev: 1.0,
+            weight_min_value: -30.0,
+            weight_max_value: 30.0,
+            weight_mutate_power: 0.5,
+            weight_mutate_rate: 0.8,
+            weight_replace_rate: 0.1,
+
+            compatibility_disjoint_coefficient: 1.0,
+            compatibility_weight_coefficient: 0.5,
+            compatibility_threshold: 3.0,
+
+            conn_add_prob: 0.5,
+            node_add_prob: 0.2,
+            enabled_mutate_rate: 0.01,
+
+            max_stagnation: 20,
+            species_elitism: 0,
+            elitism: 0,
+            survival_threshold: 0.2,
+        }
+    }
+}
+
+impl NeatPythonConf {
+    /// Parses a neat-python INI config. Missing fields keep their `Default` values, and unknown
+    /// fields are ignored.
+    pub fn parse(ini: &str) -> Result<Self, NeatPythonError> {
+        let mut values = HashMap::<(String, String), String>::new();
+        let mut section = String::new();
+        for (i, line) in ini.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if line.starts_with('[') && line.ends_with(']') {
+                section = line[1..(line.len() - 1)].trim().to_string();
+            } else if let Some(eq) = line.find('=') {
+                values.insert(
+                    (section.clone(), line[..eq].trim().to_string()),
+                    line[(eq + 1)..].trim().to_string(),
+                );
+            } else {
+                return Err(NeatPythonError::ConfigLine(i + 1));
+            }
+        }
+
+        let mut out = Self::default();
+        let parser = ConfigParser { values: &values };
+        parser.parse("NEAT", "pop_size", &mut out.pop_size)?;
+        parser.parse("NEAT", "fitness_threshold", &mut out.fitness_threshold)?;
+
+        let genome = "DefaultGenome";
+        parser.parse(genome, "num_inputs", &mut out.num_inputs)?;
+        parser.parse(genome, "num_outputs", &mut out.num_outputs)?;
+        parser.parse_bool(genome, "feed_forward", &mut out.feed_forward)?;
+        parser.parse_activation(genome, "activation_default", &mut out.activation_default)?;
+        if let Some(value) = parser.get(genome, "activation_options") {
+            out.activation_options = value
+                .split_whitespace()
+                .map(|name| {
+                    Activation::from_name(name, &[])
+                        .ok_or_else(|| parser.error(genome, "activation_options", value))
+                })
+                .collect::<Result<_, _>>()?;
+        }
+        parser.parse(
+            genome,
+            "activation_mutate_rate",
+            &mut out.activation_mutate_rate,
+        )?;
+
+        parser.parse(genome, "bias_init_mean", &mut out.bias_init_mean)?;
+        parser.parse(genome, "bias_init_stdev", &mut out.bias_init_stdev)?;
+        parser.parse(genome, "bias_min_value", &mut out.bias_min_value)?;
+        parser.parse(genome, "bias_max_value", &mut out.bias_max_value)?;
+        parser.parse(genome, "bias_mutate_power", &mut out.bias_mutate_power)?;
+        parser.parse(genome, "bias_mutate_rate", &mut out.bias_mutate_rate)?;
+
+        parser.parse(genome, "response_init_mean", &mut out.response_init_mean)?;
+        parser.parse(genome, "response_init_stdev", &mut out.response_init_stdev)?;
+        parser.parse(genome, "response_min_value", &mut out.response_min_value)?;
+        parser.parse(genome, "response_max_value", &mut out.response_max_value)?;
+        parser.parse(
+            genome,
+            "response_mutate_power",
+            &mut out.response_mutate_power,
+        )?;
+        parser.parse(
+            genome,
+            "response_mutate_rate",
+            &mut out.response_mutate_rate,
+        )?;
+
+        parser.parse(genome, "weight_init_mean", &mut out.weight_init_mean)?;
+        parser.parse(genome, "weight_init_stdev", &mut out.weight_init_stdev)?;
+        parser.parse(genome, "weight_min_value", &mut out.weight_min_value)?;
+        parser.parse(genome, "weight_max_value", &mut out.weight_max_value)?;
+        parser.parse(genome, "weight_mutate_power", &mut out.weight_mutate_power)?;
+        parser.parse(genome, "weight_mutate_rate", &mut out.weight_mutate_rate)?;
+        parser.parse(genome, "weight_replace_rate", &mut out.weight_replace_rate)?;
+
+        parser.parse(
+            genome,
+            "compatibility_disjoint_coefficient",
+            &mut out.compatibility_disjoint_coefficient,
+        )?;
+        parser.parse(
+            genome,
+            "compatibility_weight_coefficient",
+            &mut out.compatibility_weight_coefficient,
+        )?;
+        parser.parse(genome, "conn_add_prob", &mut out.conn_add_prob)?;
+        parser.parse(genome, "node_add_prob", &mut out.node_add_prob)?;
+        parser.parse(genome, "enabled_mutate_rate", &mut out.enabled_mutate_rate)?;
+
+        parser.parse(
+            "DefaultSpeciesSet",
+            "compatibility_threshold",
+            &mut out.compatibility_threshold,
+        )?;
+        parser.parse(
+            "DefaultStagnation",
+            "max_stagnation",
+            &mut out.max_stagnation,
+        )?;
+        parser.parse(
+            "DefaultStagnation",
+            "species_elitism",
+            &mut out.species_elitism,
+        )?;
+        parser.parse("DefaultReproduction", "elitism", &mut out.elitism)?;
+        parser.parse(
+            "DefaultReproduction",
+            "survival_threshold",
+            &mut out.survival_threshold,
+        )?;
+
+        Ok(out)
+    }
+}
+
+/// Looks up and parses the values of a config.
+struct ConfigParser<'a> {
+    values: &'a HashMap<(String, String), String>,
+}
+
+impl<'a> ConfigParser<'a> {
+    fn get(&self, section: &str, key: &str) -> Option<&'a str> {
+        self.values
+            .get(&(section.to_string(), key.to_string()))
+            .map(|value| value.as_str())
+    }
+
+    fn error(&self, section: &str, key: &str, value: &str) -> NeatPythonError {
+        NeatPythonError::ConfigValue {
+            section: section.to_string(),
+            key: key.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    fn parse<T: std::str::FromStr>(
+        &self,
+        section: &str,
+        key: &str,
+        out: &mut T,
+    ) -> Result<(), NeatPythonError> {
+        if let Some(value) = self.get(section, key) {
+            *out = value.parse().map_err(|_| self.error(section, key, value))?;
+        }
+        Ok(())
+    }
+
+    fn parse_bool(&self, section: &str, key: &str, out: &mut bool) -> Result<(), NeatPythonError> {
+        if let Some(value) = self.get(section, key) {
+            *out = match value.to_lowercase().as_str() {
+                "true" | "yes" | "on" | "1" => true,
+                "false" | "no" | "off" | "0" => false,
+                _ => return Err(self.error(section, key, value)),
+            };
+        }
+        Ok(())
+    }
+
+    fn parse_activation(
+        &self,
+        section: &str,
+        key: &str,
+        out: &mut Activation,
+    ) -> Result<(), NeatPythonError> {
+        if let Some(value) = self.get(section, key) {
+            *out =
+                Activation::from_name(value, &[]).ok_or_else(|| self.error(section, key, value))?;
+        }
+        Ok(())
+    }
+}
+
+/// Samples a normal distribution. A non-positive deviation gives the mean.
+fn sample_normal(mean: f64, stdev: f64, rng: &mut dyn RngCore) -> f64 {
+    if 0.0 < stdev {
+        Normal::new(mean, stdev).unwrap().sample(rng)
+    } else {
+        mean
+    }
+}
+
+impl Conf for NeatPythonConf {
+    fn get_excess_coef(&self) -> f64 {
+        self.compatibility_disjoint_coefficient
+    }
+    fn get_disjoint_coef(&self) -> f64 {
+        self.compatibility_disjoint_coefficient
+    }
+    fn get_weight_diff_coef(&self) -> f64 {
+        self.compatibility_weight_coefficient
+    }
+    fn get_compat_threshold(&self) -> f64 {
+        self.compatibility_threshold
+    }
+
+    fn get_weight_mutation_prob(&self) -> f64 {
+        self.weight_mutate_rate
+    }
+    fn get_link_addition_mutation_prob(&self) -> f64 {
+        self.conn_add_prob
+    }
+    fn get_node_addition_mutation_prob(&self) -> f64 {
+        self.node_add_prob
+    }
+    fn get_link_disable_mutation_prob(&self) -> f64 {
+        self.enabled_mutate_rate
+    }
+    fn get_complete_weight_override_prob(&self) -> f64 {
+        self.weight_replace_rate
+    }
+    fn get_activation_mutation_prob(&self) -> f64 {
+        self.activation_mutate_rate
+    }
+    fn get_bias_mutation_prob(&self) -> f64 {
+        self.bias_mutate_rate
+    }
+    fn get_response_mutation_prob(&self) -> f64 {
+        self.response_mutate_rate
+    }
+
+    fn get_default_activation(&self) -> Activation {
+        self.activation_default
+    }
+    fn get_allowed_activations(&self) -> Vec<Activation> {
+        self.activation_options.clone()
+    }
+
+    fn allow_recurrent_links(&self) -> bool {
+        !self.feed_forward
+    }
+
+    fn init_weight(&self, rng: &mut dyn RngCore) -> f64 {
+        sample_normal(self.weight_init_mean, self.weight_init_stdev, rng)
+            .max(self.weight_min_value)
+            .min(self.weight_max_value)
+    }
+    fn mutate_weight(&self, weight: &mut f64, rng: &mut dyn RngCore) {
+        *weight = (*weight + sample_normal(0.0, self.weight_mutate_power, rng))
+            .max(self.weight_min_value)
+            .min(self.weight_max_value);
+    }
+
+    fn use_node_bias(&self) -> bool {
+        true
+    }
+    fn use_node_response(&self) -> bool {
+        true
+    }
+    fn init_bias(&self, rng: &mut dyn RngCore) -> f64 {
+        sample_normal(self.bias_init_mean, self.bias_init_stdev, rng)
+            .max(self.bias_min_value)
+            .min(self.bias_max_value)
+    }
+    fn mutate_bias(&self, bias: &mut f64, rng: &mut dyn RngCore) {
+        *bias = (*bias + sample_normal(0.0, self.bias_mutate_power, rng))
+            .max(self.bias_min_value)
+            .min(self.bias_max_value);
+    }
+    fn init_response(&self, rng: &mut dyn RngCore) -> f64 {
+        sample_normal(self.response_init_mean, self.response_init_stdev, rng)
+            .max(self.response_min_value)
+            .min(self.response_max_value)
+    }
+    fn mutate_response(&self, response: &mut f64, rng: &mut dyn RngCore) {
+        *response = (*response + sample_normal(0.0, self.response_mutate_power, rng))
+            .max(self.response_min_value)
+            .min(self.response_max_value);
+    }
+
+    fn get_fitness_transform(&self) -> FitnessTransform {
+        // neat-python shares the fitness after normalizing it to the range of the population.
+        FitnessTransform::ShiftToMin
+    }
+    fn get_cull_survival_percentage(&self) -> f64 {
+        self.survival_threshold
+    }
+    fn get_staleness_threshold(&self) -> u32 {
+        self.max_stagnation
+    }
+
+    fn get_selection_strategy(&self) -> &dyn SelectionStrategy {
+        // neat-python chooses the parents uniformly from the survivors.
+        &Truncation { fraction: 1.0 }
+    }
+
+    fn get_species_elitism(&self) -> usize {
+        self.elitism
+    }
+    fn get_species_elitism_min_size(&self) -> usize {
+        0
+    }
+    fn keep_champion(&self) -> bool {
+        0 < self.species_elitism
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use crate::neat::{
+        Activation, InnovationRegistry, NeatPythonConf, NeatPythonError, NeatRng, Net, TestConf,
+    };
+
+    use super::{NeatPythonConnection, NeatPythonGenome, NeatPythonNode};
+
+    fn node(key: i64, activation: &str, bias: f64, response: f64) -> NeatPythonNode {
+        NeatPythonNode {
+            key,
+            bias,
+            response,
+            activation: activation.to_string(),
+            aggregation: "sum".to_string(),
+        }
+    }
+
+    fn connection(from: i64, to: i64, weight: f64) -> NeatPythonConnection {
+        NeatPythonConnection {
+            key: (from, to),
+            weight,
+            enabled: true,
+        }
+    }
+
+    #[test]
+    fn reused_innovations_keep_the_links_sorted() {
+        let genome = NeatPythonGenome {
+            key: 1,
+            fitness: None,
+            nodes: vec![node(0, "sigmoid", 0.0, 1.0)],
+            connections: vec![connection(-1, 0, 0.5), connection(-2, 0, -0.5)],
+        };
+        // Input -1 is node 0, input -2 is node 1 and the output is node 3, so the connection sorted
+        // first reuses the newer innovation.
+        let mut innovs = InnovationRegistry::default();
+        innovs.get_or_insert(0, 3);
+        innovs.get_or_insert(1, 3);
+
+        let conf = NeatPythonConf::default();
+        let net = Net::from_neat_python(&genome, 2, 1, &mut innovs, &conf).unwrap();
+        let numbers: Vec<usize> = net.links.iter().map(|link| link.innov).collect();
+        assert_eq!(numbers, [0, 1]);
+        assert_eq!(net.nodes[3].in_link_indices, [0, 1]);
+    }
+
+    #[test]
+    fn config_is_parsed_from_ini() {
+        let ini = "
+            # A comment.
+            [NEAT]
+            pop_size = 50
+            fitness_threshold = 3.9
+
+            [DefaultGenome]
+            num_inputs = 3
+            feed_forward = False
+            activation_default = tanh
+            activation_options = tanh sigmoid relu
+            ; Another comment.
+            conn_add_prob = 0.25
+            unknown_key = ignored
+
+            [DefaultStagnation]
+            max_stagnation = 15
+        ";
+        let conf = NeatPythonConf::parse(ini).unwrap();
+        assert_eq!(conf.pop_size, 50);
+        assert_eq!(conf.fitness_threshold, 3.9);
+        assert_eq!(conf.num_inputs, 3);
+        assert_eq!(conf.num_outputs, 1);
+        assert!(!conf.feed_forward);
+        assert_eq!(conf.activation_default, Activation::Tanh);
+        assert_eq!(
+            conf.activation_options,
+            [Activation::Tanh, Activation::Sigmoid, Activation::Relu]
+        );
+        assert_eq!(conf.conn_add_prob, 0.25);
+        assert_eq!(conf.max_stagnation, 15);
+
+        assert!(matches!(
+            NeatPythonConf::parse("[NEAT]\npop_size"),
+            Err(NeatPythonError::ConfigLine(2))
+        ));
+        assert!(matches!(
+            NeatPythonConf::parse("[NEAT]\npop_size = many"),
+            Err(NeatPythonError::ConfigValue { .. })
+        ));
+    }
+
+    /// neat-python's activation functions, with their scaled inputs.
+    fn neat_python_activation(name: &str, z: f64) -> f64 {
+        match name {
+            "sigmoid" => 1.0 / (1.0 + (-5.0 * z).exp()),
+            "tanh" => (2.5 * z).tanh(),
+            "sin" => (5.0 * z).sin(),
+            "gauss" => (-5.0 * z * z).exp(),
+            "relu" => z.max(0.0),
+            "identity" => z,
+            "abs" => z.abs(),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn activations_are_rescaled_to_neat_python() {
+        let conf = NeatPythonConf::default();
+        let names = ["sigmoid", "tanh", "sin", "gauss", "relu", "identity", "abs"];
+        for hidden in names {
+            for output in names {
+                let genome = NeatPythonGenome {
+                    key: 1,
+                    fitness: None,
+                    nodes: vec![node(0, output, 0.3, 1.2), node(1, hidden, -0.2, 0.8)],
+                    connections: vec![
+                        connection(-1, 1, 0.7),
+                        connection(-2, 1, -0.4),
+                        connection(1, 0, 1.5),
+                        connection(-1, 0, 0.9),
+                    ],
+                };
+                let mut innovs = InnovationRegistry::default();
+                let mut net = Net::from_neat_python(&genome, 2, 1, &mut innovs, &conf).unwrap();
+
+                let (x1, x2) = (0.6, -0.3);
+                let h = neat_python_activation(hidden, -0.2 + 0.8 * (0.7 * x1 - 0.4 * x2));
+                let expected = neat_python_activation(output, 0.3 + 1.2 * (1.5 * h + 0.9 * x1));
+                let actual = net.eval(&[x1, x2]).unwrap()[0];
+                assert!(
+                    (actual - expected).abs() < 1e-12,
+                    "{} {}: {} != {}",
+                    hidden,
+                    output,
+                    actual,
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn genome_round_trip_keeps_the_outputs() {
+        let conf = TestConf { threads: 1 };
+        let mut innovs = InnovationRegistry::default();
+        let mut rng = NeatRng::seed_from_u64(4);
+        let mut net = Net::new(3, 2, &mut innovs, &conf, &mut rng);
+        for _ in 0..40 {
+            net.mutate(&mut innovs, &conf, &mut rng);
+        }
+
+        let json = net.to_neat_python(7).unwrap().to_json();
+        let genome = NeatPythonGenome::from_json(&json).unwrap();
+        assert_eq!(genome.key, 7);
+        let mut back =
+            Net::from_neat_python(&genome, 3, 2, &mut innovs, &NeatPythonConf::default()).unwrap();
+        for inputs in [[0.1, 0.2, 0.3], [-1.0, 0.5, 2.0]] {
+            let expected = net.eval(&inputs).unwrap();
+            for (actual, expected) in back.eval(&inputs).unwrap().iter().zip(expected) {
+                assert!((actual - expected).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn unconnected_genome_can_mutate() {
+        let genome = NeatPythonGenome {
+            key: 1,
+            fitness: None,
+            nodes: vec![node(0, "sigmoid", 0.0, 1.0)],
+            connections: Vec::new(),
+        };
+        let conf = NeatPythonConf {
+            conn_add_prob: 0.0,
+            enabled_mutate_rate: 1.0,
+            ..NeatPythonConf::default()
+        };
+        let mut innovs = InnovationRegistry::default();
+        let mut net = Net::from_neat_python(&genome, 2, 1, &mut innovs, &conf).unwrap();
+        let mut rng = NeatRng::seed_from_u64(0);
+        for _ in 0..20 {
+            net.mutate(&mut innovs, &conf, &mut rng);
+        }
+        assert!(net.eval(&[1.0, 0.0]).is_ok());
+    }
+}
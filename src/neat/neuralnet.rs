@@ -1,6 +1,11 @@
-use crate::neat::Conf;
+use std::collections::HashSet;
+
+use crate::neat::{Activation, Conf, Innov, InnovationRegistry, NeatError};
 
 /// Smart neural network brain.
+///
+/// The nodes are the inputs, the bias, the outputs and then the hidden nodes sorted by id.
+/// Innovations connect node ids, see `Node::id`.
 pub struct Net {
     pub inputs_count: usize,
     pub outputs_count: usize,
@@ -8,11 +13,20 @@ pub struct Net {
     pub(super) links: Vec<Link>,
     pub in_species: bool,
     pub fitness: f64,
+    /// If the network may contain cycles and self links. Recurrent networks keep their node activations between evaluations.
+    pub recurrent: bool,
+    /// The activation of every node after the last time step. Only used by recurrent networks.
+    pub(super) activations: Vec<f64>,
     //pub index: usize,
 }
 
-use rand::{prelude::*, thread_rng};
+use rand::prelude::*;
 use rand_distr::{Normal, Uniform};
+use serde::{Deserialize, Serialize};
+
+/// The random pairs that `Net::mutate_link` tries in a feed-forward net before it falls back to
+/// `Net::forward_link`.
+const LINK_TRIES: usize = 20;
 
 impl Net {
     pub fn get_links_count(&self) -> usize {
@@ -30,206 +44,428 @@ impl Net {
         out
     }
     pub fn get_hidden_nodes_count(&self) -> usize {
-        self.nodes.len() - self.inputs_count - 1 - self.outputs_count
+        self.nodes.len() - self.fixed_nodes_count()
+    }
+
+    /// The number of inputs, bias and outputs. Their ids are their indices.
+    pub(super) fn fixed_nodes_count(&self) -> usize {
+        self.inputs_count + 1 + self.outputs_count
+    }
+
+    /// The id of the hidden node that splits the link with the innovation `innov`, so nets that
+    /// split the same link get the same node.
+    pub fn split_node_id(&self, innov: usize) -> usize {
+        self.fixed_nodes_count() + innov
+    }
+
+    /// Finds the index of the node with the id.
+    pub fn node_index(&self, id: usize) -> Option<usize> {
+        let fixed_nodes_count = self.fixed_nodes_count();
+        if id < fixed_nodes_count {
+            Some(id)
+        } else {
+            self.nodes[fixed_nodes_count..]
+                .binary_search_by_key(&id, |node| node.id)
+                .ok()
+                .map(|i| fixed_nodes_count + i)
+        }
+    }
+
+    /// The index of the node that the link starts in.
+    pub(super) fn link_from(&self, link: &Link) -> usize {
+        self.node_index(link.from)
+            .expect("Links connect nodes of their net.")
+    }
+
+    /// The index of the node that the link ends in.
+    pub(super) fn link_to(&self, link: &Link) -> usize {
+        self.node_index(link.to)
+            .expect("Links connect nodes of their net.")
     }
 
     /// Creates a new neural network.
     pub fn new(
         inputs_count: usize,
         outputs_count: usize,
-        innovs: &mut Vec<Innov>,
-        old_innovs_count: usize,
+        innovs: &mut InnovationRegistry,
         conf: &dyn Conf,
+        rng: &mut dyn RngCore,
     ) -> Self {
         let mut out = Self {
             nodes: Vec::with_capacity(inputs_count + outputs_count + 1),
             links: Vec::new(),
-            inputs_count: inputs_count,
-            outputs_count: outputs_count,
+            inputs_count,
+            outputs_count,
             in_species: false,
             fitness: 0.0,
+            recurrent: conf.allow_recurrent_links(),
+            activations: Vec::new(),
         };
 
         for i in 0..(inputs_count + 1) {
             out.nodes.push(Node {
                 index: i,
+                id: i,
                 in_link_indices: Vec::new(),
+                activation: Activation::Identity,
+                bias: 0.0,
+                response: 1.0,
             });
         }
 
         for i in (inputs_count + 1)..(outputs_count + inputs_count + 1) {
             out.nodes.push(Node {
                 index: i,
+                id: i,
                 in_link_indices: Vec::new(),
+                activation: conf.get_default_activation(),
+                bias: if conf.use_node_bias() {
+                    conf.init_bias(rng)
+                } else {
+                    0.0
+                },
+                response: if conf.use_node_response() {
+                    conf.init_response(rng)
+                } else {
+                    1.0
+                },
             });
 
-            for j in 0..(inputs_count + 1) {
-                out.add_link(innovs, old_innovs_count, conf.init_weight(), j, i);
-            }
-        }
-
-        out
-    }
-
-    pub fn crossover(&self, net2: &Self, conf: &dyn Conf) -> Self {
-        let mut out = self.clone();
-        let mut j = 0;
-        for (i, link) in self.links.iter().enumerate() {
-            while j < net2.links.len() && net2.links[j].innov < link.innov {
-                j += 1;
-            }
-            if !(j < net2.links.len()) {
-                break;
-            }
-            let link2 = &net2.links[j];
-            let out_link = &mut out.links[i];
-
-            if link.innov == link2.innov {
-                if Uniform::from(0.0..1.0).sample(&mut thread_rng()) < 0.5 {
-                    out_link.weight = link2.weight;
-                }
-
-                if link.enabled != link2.enabled {
-                    if Uniform::from(0.0..1.0).sample(&mut thread_rng())
-                        < conf.link_enabling_in_child_prob()
-                    {
-                        out_link.enabled = true;
-                    } else {
-                        out_link.enabled = false;
-                    }
-                }
+            // Nodes with their own bias don't need a link from the bias node.
+            let links_count = if conf.use_node_bias() {
+                inputs_count
+            } else {
+                inputs_count + 1
+            };
+            for j in 0..links_count {
+                out.add_link(innovs, conf.init_weight(rng), j, i);
             }
         }
 
         out
     }
 
-    /// adds a link betwean the two specified nodes with the specified weight.
+    /// adds a link betwean the two specified nodes with the specified weight. `from` and `to` are
+    /// node indices.
     pub fn add_link(
         &mut self,
-        innovs: &mut Vec<Innov>,
-        old_innovs_count: usize,
+        innovs: &mut InnovationRegistry,
         weight: f64,
         from: usize,
         to: usize,
     ) {
-        let mut innov = innovs.len();
-
-        for i in old_innovs_count..innov {
-            if innovs[i].from == from && innovs[i].to == to {
-                innov = i;
-                break;
-            }
-        }
-
-        if innov == innovs.len() {
-            innovs.push(Innov {
-                from: from,
-                to: to,
-                number: innov,
-            });
-        }
+        let (from_id, to_id) = (self.nodes[from].id, self.nodes[to].id);
+        let innov = innovs.get_or_insert(from_id, to_id);
 
         let link = Link {
-            innov: innov,
-            weight: weight,
+            innov,
+            from: from_id,
+            to: to_id,
+            weight,
             enabled: true,
         };
         self.nodes[to].in_link_indices.push(self.links.len());
         self.links.push(link);
     }
 
-    /// Mutates by adding a link.
-    pub fn mutate_link(
-        &mut self,
-        innovs: &mut Vec<Innov>,
-        old_innovs_count: usize,
-        conf: &dyn Conf,
-    ) {
-        let mut rng = thread_rng();
+    /// Sorts the links by innovation number and rebuilds the link indices of the nodes.
+    ///
+    /// Links are added in the order of their innovations, but renumbering them, such as when the
+    /// pending innovations of parallel reproduction are merged, can break that order.
+    pub(super) fn sort_links(&mut self) {
+        if self.links.is_sorted_by_key(|link| link.innov) {
+            return;
+        }
 
-        let mut from = Uniform::new(0, self.nodes.len() - self.outputs_count).sample(&mut rng);
-        let mut to: usize;
+        self.links.sort_by_key(|link| link.innov);
+        for node in &mut self.nodes {
+            node.in_link_indices.clear();
+        }
+        for i in 0..self.links.len() {
+            let to = self.link_to(&self.links[i]);
+            self.nodes[to].in_link_indices.push(i);
+        }
+    }
 
-        if self.inputs_count <= from {
-            from += self.outputs_count;
-            to = Uniform::new(self.inputs_count, self.nodes.len() - 1).sample(&mut rng);
-            if from <= to {
-                to += 1;
+    /// Renumbers the innovations of the current generation, and the ids of the nodes that split
+    /// them. `numbers[i]` is the new number of innovation `old_innovs_count + i`.
+    pub(super) fn renumber_innovs(&mut self, old_innovs_count: usize, numbers: &[usize]) {
+        let fixed_nodes_count = self.fixed_nodes_count();
+        let old_ids_end = fixed_nodes_count + old_innovs_count;
+        let renumber_node = |id: &mut usize| {
+            if old_ids_end <= *id {
+                *id = fixed_nodes_count + numbers[*id - old_ids_end];
+                true
+            } else {
+                false
             }
-        } else {
-            to = Uniform::new(self.inputs_count, self.nodes.len()).sample(&mut rng);
+        };
+
+        for link in &mut self.links {
+            if old_innovs_count <= link.innov {
+                link.innov = numbers[link.innov - old_innovs_count];
+            }
+            renumber_node(&mut link.from);
+            renumber_node(&mut link.to);
         }
 
-        if self.creates_cycles(from, to, innovs) {
-            let tmp = to;
-            to = from;
-            from = tmp;
+        let mut renumbered_nodes = false;
+        for node in &mut self.nodes[fixed_nodes_count..] {
+            renumbered_nodes |= renumber_node(&mut node.id);
+        }
+        if renumbered_nodes {
+            self.nodes[fixed_nodes_count..].sort_by_key(|node| node.id);
+            for (i, node) in self.nodes.iter_mut().enumerate() {
+                node.index = i;
+            }
+            self.reset_state();
         }
 
-        for i in &self.nodes[to].in_link_indices {
-            if innovs[self.links[*i].innov].from == from {
-                if !self.links[*i].enabled {
-                    self.links[*i].enabled = true;
-                    self.links[*i].weight = conf.init_weight();
+        self.sort_links();
+    }
+
+    /// Inserts a hidden node at its place in the id order and returns its index.
+    fn insert_hidden_node(
+        &mut self,
+        id: usize,
+        activation: Activation,
+        bias: f64,
+        response: f64,
+    ) -> usize {
+        let fixed_nodes_count = self.fixed_nodes_count();
+        let index = fixed_nodes_count
+            + self.nodes[fixed_nodes_count..].partition_point(|node| node.id < id);
+        self.nodes.insert(
+            index,
+            Node {
+                in_link_indices: Vec::new(),
+                index,
+                id,
+                activation,
+                bias,
+                response,
+            },
+        );
+        for (i, node) in self.nodes.iter_mut().enumerate().skip(index + 1) {
+            node.index = i;
+        }
+        if index < self.activations.len() {
+            self.activations.insert(index, 0.0);
+        }
+        index
+    }
+
+    /// Mutates by adding a link between a random valid pair of nodes. A disabled link between the
+    /// nodes is enabled again instead.
+    ///
+    /// A link is valid if it ends in an output or hidden node and the nodes aren't linked by an
+    /// enabled link yet. Recurrent networks may get cyclic and self links. Other networks stay
+    /// feed-forward, so their links start in an input, the bias or a hidden node and end in a node
+    /// that doesn't reach it. Nothing is added if there's no valid link.
+    ///
+    /// Checking a pair walks the net, so a feed-forward net tries `LINK_TRIES` random pairs and then
+    /// falls back to `Net::forward_link`, which keeps the cost linear in the size of the net. The
+    /// pair is uniform among the valid ones unless every try fails.
+    pub fn mutate_link(
+        &mut self,
+        innovs: &mut InnovationRegistry,
+        conf: &dyn Conf,
+        rng: &mut dyn RngCore,
+    ) {
+        let linked: HashSet<(usize, usize)> = self
+            .links
+            .iter()
+            .filter(|link| link.enabled)
+            .map(|link| (link.from, link.to))
+            .collect();
+
+        let mut pair = None;
+        if !self.recurrent && self.inputs_count + 1 < self.nodes.len() {
+            let sources: Vec<usize> = (0..self.nodes.len())
+                .filter(|from| !self.is_output(*from))
+                .collect();
+            let targets = Uniform::new(self.inputs_count + 1, self.nodes.len());
+            for _ in 0..LINK_TRIES {
+                let from = sources[Uniform::new(0, sources.len()).sample(rng)];
+                let to = targets.sample(rng);
+                let ids = (self.nodes[from].id, self.nodes[to].id);
+                if !linked.contains(&ids) && !self.creates_cycles(from, to) {
+                    pair = Some((from, to));
+                    break;
                 }
-                return;
             }
         }
+        let (from, to) = match pair.or_else(|| self.forward_link(&linked, rng)) {
+            Some(pair) => pair,
+            None => return,
+        };
 
-        self.add_link(innovs, old_innovs_count, conf.init_weight(), from, to);
+        let from_id = self.nodes[from].id;
+        let disabled_link = self.nodes[to]
+            .in_link_indices
+            .iter()
+            .copied()
+            .find(|i| self.links[*i].from == from_id);
+        if let Some(i) = disabled_link {
+            self.links[i].enabled = true;
+            self.links[i].weight = conf.init_weight(rng);
+        } else {
+            self.add_link(innovs, conf.init_weight(rng), from, to);
+        }
     }
 
-    /// Mutates by adding a node.
-    pub fn mutate_node(&mut self, innovs: &mut Vec<Innov>, old_innovs_count: usize) {
-        let mut rng = thread_rng();
-        let uniform = Uniform::new(0, self.links.len());
+    /// Picks a valid link for `Net::mutate_link` uniformly among the ones that go forward in
+    /// `Net::topological_order`, or among every valid link in a recurrent net. `linked` holds the
+    /// node ids of the enabled links.
+    ///
+    /// A feed-forward net has such a link whenever it has a valid link at all: the order puts the
+    /// inputs and the bias first and the outputs last, so if every forward pair were linked, every
+    /// backward pair would close a cycle.
+    fn forward_link(
+        &self,
+        linked: &HashSet<(usize, usize)>,
+        rng: &mut dyn RngCore,
+    ) -> Option<(usize, usize)> {
+        let len = self.nodes.len();
+        let order: Vec<usize> = if self.recurrent {
+            (0..len).collect()
+        } else {
+            self.topological_order()
+        };
+        let mut linked_counts = vec![0; len];
+        for (from_id, _) in linked {
+            if let Some(from) = self.node_index(*from_id) {
+                linked_counts[from] += 1;
+            }
+        }
 
-        let (from, to): (usize, usize);
-        let weight: f64;
+        // The number of unlinked targets of every position, walking the order backwards.
+        let mut targets_count = if self.recurrent {
+            len - (self.inputs_count + 1)
+        } else {
+            0
+        };
+        let mut pairs_counts = vec![0; order.len()];
+        for (position, node) in order.iter().enumerate().rev() {
+            if self.recurrent || !self.is_output(*node) {
+                pairs_counts[position] = targets_count.saturating_sub(linked_counts[*node]);
+            }
+            if !self.recurrent && self.inputs_count < *node {
+                targets_count += 1;
+            }
+        }
+        let pairs_count: usize = pairs_counts.iter().sum();
+        if pairs_count == 0 {
+            return None;
+        }
 
-        {
-            let link = &mut self.links[uniform.sample(&mut rng)];
-            from = innovs[link.innov].from;
-            to = innovs[link.innov].to;
-            weight = link.weight;
+        let mut pair = Uniform::new(0, pairs_count).sample(rng);
+        let mut position = 0;
+        while pairs_counts[position] <= pair {
+            pair -= pairs_counts[position];
+            position += 1;
         }
+        let from = order[position];
+        let targets = if self.recurrent {
+            &order[..]
+        } else {
+            &order[(position + 1)..]
+        };
+        targets
+            .iter()
+            .copied()
+            .filter(|to| self.inputs_count < *to)
+            .filter(|to| !linked.contains(&(self.nodes[from].id, self.nodes[*to].id)))
+            .nth(pair)
+            .map(|to| (from, to))
+    }
 
-        let new_index = self.nodes.len();
-        self.nodes.push(Node {
-            in_link_indices: Vec::new(),
-            index: new_index,
-        });
-        self.add_link(innovs, old_innovs_count, 1.0, from, new_index);
-        self.add_link(innovs, old_innovs_count, weight, new_index, to);
+    /// Mutates by splitting a random enabled link with a new hidden node, as in the NEAT paper. The
+    /// link is disabled and replaced by a link into the node with weight 1 and a link out of the node
+    /// with the old weight.
+    ///
+    /// The node's id comes from the split link, see `Net::split_node_id`, so links that were already
+    /// split in this net are skipped.
+    pub fn mutate_node(
+        &mut self,
+        innovs: &mut InnovationRegistry,
+        conf: &dyn Conf,
+        rng: &mut dyn RngCore,
+    ) {
+        let splittable: Vec<usize> = (0..self.links.len())
+            .filter(|i| {
+                let link = &self.links[*i];
+                link.enabled && self.node_index(self.split_node_id(link.innov)).is_none()
+            })
+            .collect();
+        if splittable.is_empty() {
+            return;
+        }
+
+        let (innov, from_id, to_id, weight) = {
+            let link = &mut self.links[splittable[Uniform::new(0, splittable.len()).sample(rng)]];
+            link.enabled = false;
+            (link.innov, link.from, link.to, link.weight)
+        };
+
+        let new_index = self.insert_hidden_node(
+            self.split_node_id(innov),
+            conf.get_default_activation(),
+            0.0,
+            1.0,
+        );
+        let from = self.node_index(from_id).unwrap();
+        let to = self.node_index(to_id).unwrap();
+        self.add_link(innovs, 1.0, from, new_index);
+        self.add_link(innovs, weight, new_index, to);
     }
 
-    pub fn mutate(&mut self, innovs: &mut Vec<Innov>, old_innovs_count: usize, conf: &dyn Conf) {
-        if Uniform::from(0.0..1.0).sample(&mut thread_rng())
-            < conf.get_link_addition_mutation_prob()
-        {
-            self.mutate_link(innovs, old_innovs_count, conf);
+    pub fn mutate(
+        &mut self,
+        innovs: &mut InnovationRegistry,
+        conf: &dyn Conf,
+        rng: &mut dyn RngCore,
+    ) {
+        if Uniform::from(0.0..1.0).sample(rng) < conf.get_link_addition_mutation_prob() {
+            self.mutate_link(innovs, conf, rng);
         }
 
-        if Uniform::from(0.0..1.0).sample(&mut thread_rng())
-            < conf.get_node_addition_mutation_prob()
-        {
-            self.mutate_node(innovs, old_innovs_count);
+        if Uniform::from(0.0..1.0).sample(rng) < conf.get_node_addition_mutation_prob() {
+            self.mutate_node(innovs, conf, rng);
         }
 
-        if Uniform::from(0.0..1.0).sample(&mut thread_rng()) < conf.get_weight_mutation_prob() {
+        if Uniform::from(0.0..1.0).sample(rng) < conf.get_activation_mutation_prob() {
+            self.mutate_activation(conf, rng);
+        }
+
+        if Uniform::from(0.0..1.0).sample(rng) < conf.get_weight_mutation_prob() {
             for link in &mut self.links {
-                link.mutate_weight(conf);
+                link.mutate_weight(conf, rng);
             }
         }
 
-        if Uniform::from(0.0..1.0).sample(&mut thread_rng()) < conf.get_link_disable_mutation_prob()
+        if conf.use_node_bias()
+            && Uniform::from(0.0..1.0).sample(rng) < conf.get_bias_mutation_prob()
         {
-            let tries_count = std::cmp::min(self.links.len() - 1, 12);
+            for node in &mut self.nodes[(self.inputs_count + 1)..] {
+                node.mutate_bias(conf, rng);
+            }
+        }
+
+        if conf.use_node_response()
+            && Uniform::from(0.0..1.0).sample(rng) < conf.get_response_mutation_prob()
+        {
+            for node in &mut self.nodes[(self.inputs_count + 1)..] {
+                node.mutate_response(conf, rng);
+            }
+        }
+
+        if Uniform::from(0.0..1.0).sample(rng) < conf.get_link_disable_mutation_prob() {
+            // A net without links, such as an unconnected neat-python genome, has none to disable.
+            let tries_count = std::cmp::min(self.links.len().saturating_sub(1), 12);
             let mut tried = Vec::<usize>::with_capacity(tries_count);
             for i in 0..tries_count {
-                let mut link_idx =
-                    Uniform::from(0..(self.links.len() - i)).sample(&mut thread_rng());
+                let mut link_idx = Uniform::from(0..(self.links.len() - i)).sample(rng);
                 for tried_idx in &tried {
                     if *tried_idx <= link_idx {
                         link_idx += 1;
@@ -246,47 +482,181 @@ impl Net {
         }
     }
 
+    /// Mutates by replacing the activation function of a random hidden or output node with one of
+    /// `Conf::get_allowed_activations`.
+    pub fn mutate_activation(&mut self, conf: &dyn Conf, rng: &mut dyn RngCore) {
+        let allowed = conf.get_allowed_activations();
+        if allowed.is_empty() || self.nodes.len() <= self.inputs_count + 1 {
+            return;
+        }
+
+        let node_index = Uniform::new(self.inputs_count + 1, self.nodes.len()).sample(rng);
+        self.nodes[node_index].activation = allowed[Uniform::new(0, allowed.len()).sample(rng)];
+    }
+
     /// Evaluates the network.
-    pub fn eval(&self, inputs: &[f64], innovs: &Vec<Innov>) -> Vec<f64> {
+    ///
+    /// A recurrent network advances by a single time step, see `Net::eval_step`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `NeatError::InputLength` if `inputs` doesn't have one value per input.
+    pub fn eval(&mut self, inputs: &[f64]) -> Result<Vec<f64>, NeatError> {
+        if self.recurrent {
+            self.eval_step(inputs)
+        } else {
+            self.eval_feed_forward(inputs)
+        }
+    }
+
+    /// Evaluates a feed-forward network. The network doesn't keep any state between calls.
+    pub fn eval_feed_forward(&self, inputs: &[f64]) -> Result<Vec<f64>, NeatError> {
+        self.check_inputs(inputs)?;
+
         let mut out = Vec::<f64>::with_capacity(self.outputs_count);
         let mut evaled_nodes = Vec::<(f64, bool)>::with_capacity(self.nodes.len());
         evaled_nodes.resize(self.nodes.len(), (0.0, false));
 
         for i in 0..self.outputs_count {
-            out.push(self.nodes[i + self.inputs_count + 1].eval(
-                self,
-                &mut evaled_nodes,
-                inputs,
-                innovs,
-            ));
+            out.push(self.nodes[i + self.inputs_count + 1].eval(self, &mut evaled_nodes, inputs));
         }
 
-        out
+        Ok(out)
+    }
+
+    /// Advances the network by a single time step and returns the outputs.
+    ///
+    /// The inputs and the bias are set first, then every other node is updated synchronously
+    /// from the activations of the previous time step. A signal therefore needs one step per link to
+    /// travel through the network.
+    pub fn eval_step(&mut self, inputs: &[f64]) -> Result<Vec<f64>, NeatError> {
+        self.check_inputs(inputs)?;
+
+        self.activations.resize(self.nodes.len(), 0.0);
+        self.activations[..self.inputs_count].copy_from_slice(&inputs[..self.inputs_count]);
+        self.activations[self.inputs_count] = 1.0;
+
+        let mut next_activations = self.activations.clone();
+        for node in &self.nodes[(self.inputs_count + 1)..] {
+            let mut sum = 0.0;
+            for link_index in &node.in_link_indices {
+                let link = &self.links[*link_index];
+                if link.enabled {
+                    sum += self.activations[self.link_from(link)] * link.weight;
+                }
+            }
+            next_activations[node.index] = node.activate(sum);
+        }
+        self.activations = next_activations;
+
+        Ok(
+            self.activations[(self.inputs_count + 1)..(self.inputs_count + 1 + self.outputs_count)]
+                .to_vec(),
+        )
+    }
+
+    /// Checks that there's a value for every input.
+    pub(super) fn check_inputs(&self, inputs: &[f64]) -> Result<(), NeatError> {
+        if inputs.len() == self.inputs_count {
+            Ok(())
+        } else {
+            Err(NeatError::InputLength {
+                expected: self.inputs_count,
+                actual: inputs.len(),
+            })
+        }
+    }
+
+    /// Clears the activations that a recurrent network remembers between evaluations.
+    pub fn reset_state(&mut self) {
+        self.activations.clear();
     }
 
     /// Checks if adding a link from `from` to `to` will creates cycles, and therefore makes the network unevaluable.
-    pub fn creates_cycles(&self, from: usize, to: usize, innovs: &Vec<Innov>) -> bool {
-        let mut visited_nodes = vec![to];
-        loop {
-            let mut newly_visited_nodes_count = 0;
-            for link in &self.links {
-                let link_from = innovs[link.innov].from;
-                let link_to = innovs[link.innov].to;
-
-                if visited_nodes.contains(&link_from) && !visited_nodes.contains(&link_to) {
-                    if link_to == from {
-                        return true;
-                    }
+    /// `from` and `to` are node indices.
+    pub fn creates_cycles(&self, from: usize, to: usize) -> bool {
+        self.reaching_nodes(from)[to]
+    }
 
-                    visited_nodes.push(link_to);
-                    newly_visited_nodes_count += 1;
+    /// Checks if the links, including the disabled ones, form a cycle.
+    pub(super) fn has_cycle(&self) -> bool {
+        self.topological_order().len() < self.nodes.len()
+    }
+
+    /// Orders the node indices so that every link, including the disabled ones, goes forward. The
+    /// outputs come as late as the links allow, after every other node unless they have out links.
+    /// The nodes on or after a cycle are left out.
+    fn topological_order(&self) -> Vec<usize> {
+        let mut successors = vec![Vec::new(); self.nodes.len()];
+        let mut in_links_counts = vec![0usize; self.nodes.len()];
+        for link in &self.links {
+            let to = self.link_to(link);
+            successors[self.link_from(link)].push(to);
+            in_links_counts[to] += 1;
+        }
+
+        // Removes the nodes without in links, the inputs and the bias first and the outputs while
+        // there's nothing else.
+        let (mut inputs, mut ready, mut outputs) = (Vec::new(), Vec::new(), Vec::new());
+        for i in (0..self.nodes.len()).rev() {
+            if in_links_counts[i] != 0 {
+                continue;
+            }
+            if i <= self.inputs_count {
+                inputs.push(i);
+            } else if self.is_output(i) {
+                outputs.push(i);
+            } else {
+                ready.push(i);
+            }
+        }
+        let mut order = Vec::with_capacity(self.nodes.len());
+        while let Some(node) = inputs
+            .pop()
+            .or_else(|| ready.pop())
+            .or_else(|| outputs.pop())
+        {
+            #[cfg(test)]
+            tests::count_walk(1 + successors[node].len());
+            order.push(node);
+            for next in &successors[node] {
+                in_links_counts[*next] -= 1;
+                if in_links_counts[*next] == 0 {
+                    if self.is_output(*next) {
+                        outputs.push(*next);
+                    } else {
+                        ready.push(*next);
+                    }
                 }
             }
+        }
+        order
+    }
+
+    fn is_output(&self, index: usize) -> bool {
+        self.inputs_count < index && index < self.fixed_nodes_count()
+    }
 
-            if newly_visited_nodes_count == 0 {
-                return false;
+    /// Marks the nodes that have a path to the node `index`, including the node itself. Disabled
+    /// links count too, since they can be enabled again.
+    ///
+    /// Walks the in links of the nodes backwards, visiting every node and link at most once.
+    pub(super) fn reaching_nodes(&self, index: usize) -> Vec<bool> {
+        let mut reaching = vec![false; self.nodes.len()];
+        reaching[index] = true;
+        let mut stack = vec![index];
+        while let Some(node) = stack.pop() {
+            #[cfg(test)]
+            tests::count_walk(1 + self.nodes[node].in_link_indices.len());
+            for link_index in &self.nodes[node].in_link_indices {
+                let from = self.link_from(&self.links[*link_index]);
+                if !reaching[from] {
+                    reaching[from] = true;
+                    stack.push(from);
+                }
             }
         }
+        reaching
     }
 }
 
@@ -300,14 +670,20 @@ impl Clone for Net {
             links: self.links.clone(),
             nodes: self.nodes.clone(),
             fitness: self.fitness,
+            recurrent: self.recurrent,
+            activations: self.activations.clone(),
         }
     }
 }
 
 /// Connects two nodes with weight.
 pub(super) struct Link {
-    /// The innovation number. The matching innovation contains information about the two connected nodes.
+    /// The innovation number, which marks the link as the same in every net that has it.
     pub innov: usize,
+    /// The id of the node that the link starts in, see `Node::id`.
+    pub from: usize,
+    /// The id of the node that the link ends in.
+    pub to: usize,
     /// The weight of the link.
     pub weight: f64,
     /// If this is enabled
@@ -316,14 +692,12 @@ pub(super) struct Link {
 
 impl Link {
     /// Mutates the weight.
-    pub fn mutate_weight(&mut self, conf: &dyn Conf) {
+    pub fn mutate_weight(&mut self, conf: &dyn Conf, rng: &mut dyn RngCore) {
         if self.enabled {
-            if Uniform::from(0.0..1.0).sample(&mut thread_rng())
-                < conf.get_complete_weight_override_prob()
-            {
-                self.weight = conf.init_weight();
+            if Uniform::from(0.0..1.0).sample(rng) < conf.get_complete_weight_override_prob() {
+                self.weight = conf.init_weight(rng);
             } else {
-                conf.mutate_weight(&mut self.weight);
+                conf.mutate_weight(&mut self.weight, rng);
             }
         }
     }
@@ -333,6 +707,8 @@ impl Clone for Link {
     fn clone(&self) -> Self {
         Self {
             innov: self.innov,
+            from: self.from,
+            to: self.to,
             weight: self.weight,
             enabled: self.enabled,
         }
@@ -345,19 +721,31 @@ pub(super) struct Node {
     pub in_link_indices: Vec<usize>,
     /// The index of the node.
     pub index: usize,
+    /// The historical identifier of the node, which is the same in every net that has it.
+    /// Innovations connect ids rather than indices.
+    ///
+    /// The inputs, the bias and the outputs have their index as id, and hidden nodes get theirs
+    /// from the link they split, see `Net::split_node_id`.
+    pub id: usize,
+    /// The activation function of the node. Ignored for the inputs and the bias.
+    pub activation: Activation,
+    /// Added to the weighted sum of the inputs. Stays 0 unless `Conf::use_node_bias` is set.
+    pub bias: f64,
+    /// Multiplies the weighted sum of the inputs. Stays 1 unless `Conf::use_node_response` is set.
+    pub response: f64,
 }
 
 impl Node {
     /// Finds a link by its innovations number. The method return the links index in `self.in_links`.
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// Panics if the a link with the specified innovation number wasn't found in `self.in_links`.
-    pub fn find_link(&mut self, innov: usize, net: &Net) -> usize {
-        let mut right_boundary = self.in_link_indices.len() - 1;
+    /// Returns `NeatError::MissingLink` if the a link with the specified innovation number wasn't found in `self.in_links`.
+    pub fn find_link(&self, innov: usize, net: &Net) -> Result<usize, NeatError> {
+        let mut right_boundary = self.in_link_indices.len();
         let mut left_boundary = 0;
         let mut middle;
-        while left_boundary <= right_boundary {
+        while left_boundary < right_boundary {
             middle = (left_boundary + right_boundary) / 2;
 
             let middle_innov = net.links[self.in_link_indices[middle]].innov;
@@ -365,27 +753,40 @@ impl Node {
             if middle_innov < innov {
                 left_boundary = middle + 1
             } else if innov < middle_innov {
-                right_boundary = middle - 1;
+                right_boundary = middle;
             } else {
-                return middle;
+                return Ok(middle);
             }
         }
 
-        panic!("Link not found!");
+        Err(NeatError::MissingLink { innov })
+    }
+
+    /// Applies the bias, the response and the activation function to the weighted sum of the inputs.
+    pub fn activate(&self, sum: f64) -> f64 {
+        self.activation.apply(self.bias + self.response * sum)
     }
 
-    pub fn activate(x: f64) -> f64 {
-        1.0 / (1.0 + (-4.9 * x).exp())
+    /// Mutates the bias.
+    pub fn mutate_bias(&mut self, conf: &dyn Conf, rng: &mut dyn RngCore) {
+        if Uniform::from(0.0..1.0).sample(rng) < conf.get_complete_weight_override_prob() {
+            self.bias = conf.init_bias(rng);
+        } else {
+            conf.mutate_bias(&mut self.bias, rng);
+        }
+    }
+
+    /// Mutates the response.
+    pub fn mutate_response(&mut self, conf: &dyn Conf, rng: &mut dyn RngCore) {
+        if Uniform::from(0.0..1.0).sample(rng) < conf.get_complete_weight_override_prob() {
+            self.response = conf.init_response(rng);
+        } else {
+            conf.mutate_response(&mut self.response, rng);
+        }
     }
 
     /// evaluate this node
-    pub fn eval(
-        &self,
-        net: &Net,
-        evaled_nodes: &mut Vec<(f64, bool)>,
-        inputs: &[f64],
-        innovs: &Vec<Innov>,
-    ) -> f64 {
+    pub fn eval(&self, net: &Net, evaled_nodes: &mut [(f64, bool)], inputs: &[f64]) -> f64 {
         if self.index < net.inputs_count {
             inputs[self.index]
         } else if self.index == net.inputs_count {
@@ -395,20 +796,16 @@ impl Node {
             for link_index in &self.in_link_indices {
                 let link = &net.links[*link_index];
                 if link.enabled {
-                    if evaled_nodes[self.index].1 {
-                        sum += evaled_nodes[innovs[link.innov].from].0 * link.weight;
+                    let from = net.link_from(link);
+                    if evaled_nodes[from].1 {
+                        sum += evaled_nodes[from].0 * link.weight;
                     } else {
-                        sum += net.nodes[innovs[link.innov].from].eval(
-                            net,
-                            evaled_nodes,
-                            inputs,
-                            innovs,
-                        ) * link.weight;
+                        sum += net.nodes[from].eval(net, evaled_nodes, inputs) * link.weight;
                     }
                 }
             }
 
-            evaled_nodes[self.index] = (Self::activate(sum), true);
+            evaled_nodes[self.index] = (self.activate(sum), true);
 
             evaled_nodes[self.index].0
         }
@@ -420,23 +817,100 @@ impl Clone for Node {
         Self {
             in_link_indices: self.in_link_indices.clone(),
             index: self.index,
+            id: self.id,
+            activation: self.activation,
+            bias: self.bias,
+            response: self.response,
         }
     }
 }
 
-/// The innovation object.
-pub struct Innov {
-    pub from: usize,
-    pub to: usize,
-    pub number: usize,
-}
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
 
-impl Clone for Innov {
-    fn clone(&self) -> Self {
-        Self {
-            from: self.from,
-            to: self.to,
-            number: self.number,
+    use rand::SeedableRng;
+
+    use super::LINK_TRIES;
+    use crate::neat::{InnovationRegistry, NeatRng, Net, TestConf};
+
+    thread_local! {
+        /// The nodes and links visited by the walks over the net.
+        static WALKED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    pub(super) fn count_walk(steps: usize) {
+        WALKED.with(|walked| walked.set(walked.get() + steps));
+    }
+
+    /// Adds a link and checks that it walked the net a bounded number of times.
+    fn mutate_link_linearly(
+        net: &mut Net,
+        innovs: &mut InnovationRegistry,
+        conf: &TestConf,
+        rng: &mut NeatRng,
+    ) {
+        let size = net.nodes.len() + net.links.len();
+        WALKED.with(|walked| walked.set(0));
+        net.mutate_link(innovs, conf, rng);
+        let walked = WALKED.with(Cell::get);
+        assert!(walked <= (LINK_TRIES + 1) * size, "{} > {}", walked, size);
+    }
+
+    #[test]
+    fn mutate_link_adds_a_valid_link_while_there_is_one() {
+        let conf = TestConf { threads: 1 };
+        let mut innovs = InnovationRegistry::default();
+        let mut rng = NeatRng::seed_from_u64(1);
+        let mut net = Net::new(3, 2, &mut innovs, &conf, &mut rng);
+        for _ in 0..10 {
+            net.mutate_node(&mut innovs, &conf, &mut rng);
+        }
+
+        loop {
+            let enabled_count = net.get_enabled_links_count();
+            mutate_link_linearly(&mut net, &mut innovs, &conf, &mut rng);
+            assert!(!net.has_cycle());
+            for link in &net.links {
+                assert!(net.inputs_count < net.link_to(link));
+            }
+            if net.get_enabled_links_count() == enabled_count {
+                break;
+            }
+            assert_eq!(net.get_enabled_links_count(), enabled_count + 1);
+        }
+
+        // Every remaining pair would make a cycle or is linked already.
+        let len = net.nodes.len();
+        for from in 0..len {
+            for to in (net.inputs_count + 1)..len {
+                let is_output = net.inputs_count < from && from < net.fixed_nodes_count();
+                let linked = net.links.iter().any(|link| {
+                    link.enabled && link.from == net.nodes[from].id && link.to == net.nodes[to].id
+                });
+                assert!(is_output || linked || net.creates_cycles(from, to));
+            }
+        }
+    }
+
+    #[test]
+    fn mutate_link_cost_is_linear_in_the_net_size() {
+        let conf = TestConf { threads: 1 };
+        let mut innovs = InnovationRegistry::default();
+        let mut rng = NeatRng::seed_from_u64(2);
+        let mut net = Net::new(10, 5, &mut innovs, &conf, &mut rng);
+        while net.nodes.len() < 1500 {
+            net.mutate_node(&mut innovs, &conf, &mut rng);
+            if net.nodes.len().is_multiple_of(2) {
+                net.mutate_link(&mut innovs, &conf, &mut rng);
+            }
+        }
+
+        for _ in 0..100 {
+            let enabled_count = net.get_enabled_links_count();
+            mutate_link_linearly(&mut net, &mut innovs, &conf, &mut rng);
+            assert_eq!(net.get_enabled_links_count(), enabled_count + 1);
         }
+        assert!(!net.has_cycle());
     }
 }
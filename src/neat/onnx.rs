@@ -0,0 +1,645 @@
+use std::{collections::HashMap, fmt, fs, io, path::Path};
+
+use crate::neat::{Activation, CompiledNet, NeatError, Net};
+
+/// The ONNX IR version of the exported models.
+const IR_VERSION: i64 = 7;
+/// The version of the default operator set the exported models use.
+const OPSET_VERSION: i64 = 13;
+
+/// `TensorProto.DataType.INT64`
+const INT64: i32 = 7;
+/// `TensorProto.DataType.DOUBLE`
+const DOUBLE: i32 = 11;
+
+/// `AttributeProto.AttributeType.INT`
+const ATTRIBUTE_INT: i32 = 2;
+
+/// The error returned when a network can't be exported to ONNX.
+#[derive(Debug)]
+pub enum OnnxError {
+    Io(io::Error),
+    /// Custom activation functions have no ONNX equivalent.
+    UnsupportedActivation(String),
+}
+
+impl fmt::Display for OnnxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OnnxError::Io(err) => write!(f, "io error: {}", err),
+            OnnxError::UnsupportedActivation(name) => {
+                write!(f, "activation `{}` has no ONNX equivalent", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for OnnxError {}
+
+impl From<io::Error> for OnnxError {
+    fn from(err: io::Error) -> Self {
+        OnnxError::Io(err)
+    }
+}
+
+/// A minimal protobuf message encoder.
+#[derive(Default)]
+struct ProtoWriter {
+    buf: Vec<u8>,
+}
+
+impl ProtoWriter {
+    fn varint(&mut self, mut value: u64) {
+        while 0x80 <= value {
+            self.buf.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        self.buf.push(value as u8);
+    }
+
+    fn key(&mut self, field: u32, wire_type: u32) {
+        self.varint(u64::from(field << 3 | wire_type));
+    }
+
+    fn int(&mut self, field: u32, value: i64) {
+        self.key(field, 0);
+        self.varint(value as u64);
+    }
+
+    fn bytes(&mut self, field: u32, value: &[u8]) {
+        self.key(field, 2);
+        self.varint(value.len() as u64);
+        self.buf.extend_from_slice(value);
+    }
+
+    fn string(&mut self, field: u32, value: &str) {
+        self.bytes(field, value.as_bytes());
+    }
+
+    fn message(&mut self, field: u32, encode: impl FnOnce(&mut ProtoWriter)) {
+        let mut inner = ProtoWriter::default();
+        encode(&mut inner);
+        self.bytes(field, &inner.buf);
+    }
+}
+
+/// An operator of the graph.
+struct OnnxNode {
+    op_type: &'static str,
+    inputs: Vec<String>,
+    output: String,
+    /// Integer attributes.
+    attributes: Vec<(&'static str, i64)>,
+}
+
+/// A constant tensor of the graph.
+struct Initializer {
+    name: String,
+    dims: Vec<i64>,
+    data_type: i32,
+    raw_data: Vec<u8>,
+}
+
+impl Initializer {
+    fn doubles(name: String, dims: Vec<i64>, values: &[f64]) -> Self {
+        Self {
+            name,
+            dims,
+            data_type: DOUBLE,
+            raw_data: values
+                .iter()
+                .flat_map(|value| value.to_le_bytes())
+                .collect(),
+        }
+    }
+
+    fn int64s(name: String, dims: Vec<i64>, values: &[i64]) -> Self {
+        Self {
+            name,
+            dims,
+            data_type: INT64,
+            raw_data: values
+                .iter()
+                .flat_map(|value| value.to_le_bytes())
+                .collect(),
+        }
+    }
+}
+
+/// Builds the graph while keeping the names of the values unique.
+#[derive(Default)]
+struct GraphBuilder {
+    nodes: Vec<OnnxNode>,
+    initializers: Vec<Initializer>,
+}
+
+impl GraphBuilder {
+    fn op(&mut self, op_type: &'static str, inputs: Vec<String>, output: String) -> String {
+        self.op_with_attributes(op_type, inputs, output, Vec::new())
+    }
+
+    fn op_with_attributes(
+        &mut self,
+        op_type: &'static str,
+        inputs: Vec<String>,
+        output: String,
+        attributes: Vec<(&'static str, i64)>,
+    ) -> String {
+        self.nodes.push(OnnxNode {
+            op_type,
+            inputs,
+            output: output.clone(),
+            attributes,
+        });
+        output
+    }
+
+    /// Adds a scalar double constant.
+    fn scalar(&mut self, name: String, value: f64) -> String {
+        self.initializers
+            .push(Initializer::doubles(name.clone(), Vec::new(), &[value]));
+        name
+    }
+
+    /// Applies an activation function to `x`, using only standard operators.
+    fn activation(
+        &mut self,
+        activation: Activation,
+        x: String,
+        prefix: &str,
+    ) -> Result<String, OnnxError> {
+        let output = format!("{}_act", prefix);
+        Ok(match activation {
+            Activation::Sigmoid => {
+                let slope = self.scalar(format!("{}_slope", prefix), 4.9);
+                let steep = self.op("Mul", vec![x, slope], format!("{}_steep", prefix));
+                self.op("Sigmoid", vec![steep], output)
+            }
+            Activation::Tanh => self.op("Tanh", vec![x], output),
+            Activation::Relu => self.op("Relu", vec![x], output),
+            Activation::Identity => self.op("Identity", vec![x], output),
+            Activation::Sine => self.op("Sin", vec![x], output),
+            Activation::Gaussian => {
+                let square = self.op("Mul", vec![x.clone(), x], format!("{}_square", prefix));
+                let neg = self.op("Neg", vec![square], format!("{}_neg", prefix));
+                self.op("Exp", vec![neg], output)
+            }
+            Activation::Abs => self.op("Abs", vec![x], output),
+            Activation::Step => {
+                let zero = self.scalar(format!("{}_zero", prefix), 0.0);
+                let positive = self.op("Greater", vec![x, zero], format!("{}_positive", prefix));
+                self.op_with_attributes(
+                    "Cast",
+                    vec![positive],
+                    output,
+                    vec![("to", i64::from(DOUBLE))],
+                )
+            }
+            Activation::Custom(custom) => {
+                return Err(OnnxError::UnsupportedActivation(custom.name.to_string()))
+            }
+        })
+    }
+}
+
+/// Encodes a `[batch, width]` double tensor description.
+fn encode_value_info(proto: &mut ProtoWriter, field: u32, name: &str, width: usize) {
+    proto.message(field, |value_info| {
+        value_info.string(1, name);
+        value_info.message(2, |type_proto| {
+            type_proto.message(1, |tensor_type| {
+                tensor_type.int(1, i64::from(DOUBLE));
+                tensor_type.message(2, |shape| {
+                    shape.message(1, |dim| dim.string(2, "batch"));
+                    shape.message(1, |dim| dim.int(1, width as i64));
+                });
+            });
+        });
+    });
+}
+
+impl CompiledNet {
+    /// Exports the network as a serialized ONNX model with a `[batch, inputs_count]` double input
+    /// named `inputs` and a `[batch, outputs_count]` double output named `outputs`.
+    ///
+    /// Nodes are grouped into layers by their depth, the length of the longest path from an input.
+    /// Every layer is split further by activation function, and each group becomes a `MatMul`
+    /// against a weight initializer followed by the activation. The outputs of every group are
+    /// concatenated to the inputs, so later layers can use links that skip layers. The steepened
+    /// sigmoid becomes `Sigmoid(4.9 * x)`.
+    pub fn to_onnx(&self) -> Result<Vec<u8>, OnnxError> {
+        let graph = self.onnx_graph()?;
+
+        let mut model = ProtoWriter::default();
+        model.int(1, IR_VERSION);
+        model.string(2, "neat");
+        model.string(3, env!("CARGO_PKG_VERSION"));
+        model.message(7, |proto| {
+            for node in &graph.nodes {
+                proto.message(1, |proto| {
+                    for input in &node.inputs {
+                        proto.string(1, input);
+                    }
+                    proto.string(2, &node.output);
+                    proto.string(3, &node.output);
+                    proto.string(4, node.op_type);
+                    for (name, value) in &node.attributes {
+                        proto.message(5, |attribute| {
+                            attribute.string(1, name);
+                            attribute.int(3, *value);
+                            attribute.int(20, i64::from(ATTRIBUTE_INT));
+                        });
+                    }
+                });
+            }
+            proto.string(2, "net");
+            for initializer in &graph.initializers {
+                proto.message(5, |tensor| {
+                    for dim in &initializer.dims {
+                        tensor.int(1, *dim);
+                    }
+                    tensor.int(2, i64::from(initializer.data_type));
+                    tensor.string(8, &initializer.name);
+                    tensor.bytes(9, &initializer.raw_data);
+                });
+            }
+            encode_value_info(proto, 11, "inputs", self.inputs_count);
+            encode_value_info(proto, 12, "outputs", self.outputs_count);
+        });
+        model.message(8, |opset| {
+            opset.string(1, "");
+            opset.int(2, OPSET_VERSION);
+        });
+
+        Ok(model.buf)
+    }
+
+    /// Builds the operators and initializers of `CompiledNet::to_onnx`.
+    fn onnx_graph(&self) -> Result<GraphBuilder, OnnxError> {
+        let nodes = self.nodes();
+        let links = self.links();
+
+        let mut depths = HashMap::<usize, usize>::new();
+        let mut groups = Vec::<(usize, Activation, Vec<usize>)>::new();
+        let mut links_start = 0;
+        let mut links_starts = Vec::with_capacity(nodes.len());
+        for (i, node) in nodes.iter().enumerate() {
+            let depth = 1 + links[links_start..node.links_end]
+                .iter()
+                .map(|link| depths.get(&link.from).copied().unwrap_or(0))
+                .max()
+                .unwrap_or(0);
+            depths.insert(node.index, depth);
+            links_starts.push(links_start);
+            links_start = node.links_end;
+
+            match groups
+                .iter_mut()
+                .find(|(d, activation, _)| *d == depth && *activation == node.activation)
+            {
+                Some((_, _, members)) => members.push(i),
+                None => groups.push((depth, node.activation, vec![i])),
+            }
+        }
+        groups.sort_by_key(|(depth, _, _)| *depth);
+
+        let mut graph = GraphBuilder::default();
+        // The columns of the current state tensor. It starts as the inputs and every group appends
+        // its activations.
+        let mut columns = HashMap::<usize, usize>::new();
+        for i in 0..self.inputs_count {
+            columns.insert(i, i);
+        }
+        let mut state = "inputs".to_string();
+
+        for (group_index, (_, activation, members)) in groups.iter().enumerate() {
+            let prefix = format!("g{}", group_index);
+            let width = columns.len();
+
+            let mut weights = vec![0.0; width * members.len()];
+            let mut bias_weights = vec![0.0; members.len()];
+            let mut responses = Vec::with_capacity(members.len());
+            let mut biases = Vec::with_capacity(members.len());
+            for (j, member) in members.iter().enumerate() {
+                let node = &nodes[*member];
+                for link in &links[links_starts[*member]..node.links_end] {
+                    if link.from == self.inputs_count {
+                        bias_weights[j] += link.weight;
+                    } else {
+                        weights[columns[&link.from] * members.len() + j] += link.weight;
+                    }
+                }
+                responses.push(node.response);
+                biases.push(node.bias);
+            }
+
+            let weights_name = format!("{}_weights", prefix);
+            graph.initializers.push(Initializer::doubles(
+                weights_name.clone(),
+                vec![width as i64, members.len() as i64],
+                &weights,
+            ));
+            let mut x = graph.op(
+                "MatMul",
+                vec![state.clone(), weights_name],
+                format!("{}_matmul", prefix),
+            );
+
+            if bias_weights.iter().any(|weight| *weight != 0.0) {
+                let name = format!("{}_bias_weights", prefix);
+                graph.initializers.push(Initializer::doubles(
+                    name.clone(),
+                    vec![members.len() as i64],
+                    &bias_weights,
+                ));
+                x = graph.op("Add", vec![x, name], format!("{}_sum", prefix));
+            }
+            if responses.iter().any(|response| *response != 1.0) {
+                let name = format!("{}_responses", prefix);
+                graph.initializers.push(Initializer::doubles(
+                    name.clone(),
+                    vec![members.len() as i64],
+                    &responses,
+                ));
+                x = graph.op("Mul", vec![x, name], format!("{}_response", prefix));
+            }
+            if biases.iter().any(|bias| *bias != 0.0) {
+                let name = format!("{}_biases", prefix);
+                graph.initializers.push(Initializer::doubles(
+                    name.clone(),
+                    vec![members.len() as i64],
+                    &biases,
+                ));
+                x = graph.op("Add", vec![x, name], format!("{}_biased", prefix));
+            }
+
+            let activations = graph.activation(*activation, x, &prefix)?;
+            state = graph.op_with_attributes(
+                "Concat",
+                vec![state, activations],
+                format!("{}_state", prefix),
+                vec![("axis", 1)],
+            );
+            for member in members {
+                columns.insert(nodes[*member].index, columns.len());
+            }
+        }
+
+        let outputs_start = self.inputs_count + 1;
+        let output_columns: Vec<i64> = (outputs_start..(outputs_start + self.outputs_count))
+            .map(|output| columns[&output] as i64)
+            .collect();
+        graph.initializers.push(Initializer::int64s(
+            "output_columns".to_string(),
+            vec![self.outputs_count as i64],
+            &output_columns,
+        ));
+        graph.op_with_attributes(
+            "Gather",
+            vec![state, "output_columns".to_string()],
+            "outputs".to_string(),
+            vec![("axis", 1)],
+        );
+
+        Ok(graph)
+    }
+
+    /// Writes the network to an ONNX model file, see `CompiledNet::to_onnx`.
+    pub fn save_onnx(&self, path: impl AsRef<Path>) -> Result<(), OnnxError> {
+        fs::write(path, self.to_onnx()?)?;
+        Ok(())
+    }
+}
+
+impl Net {
+    /// Compiles the network and exports it to ONNX, see `CompiledNet::to_onnx`.
+    pub fn to_onnx(&self) -> Result<Vec<u8>, NeatError> {
+        Ok(self.compile()?.to_onnx()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, convert::TryInto};
+
+    use rand::SeedableRng;
+
+    use super::{GraphBuilder, ProtoWriter, DOUBLE};
+    use crate::neat::{Activation, CompiledNet, InnovationRegistry, NeatRng, Net, TestConf};
+
+    fn encoded(encode: impl FnOnce(&mut ProtoWriter)) -> Vec<u8> {
+        let mut proto = ProtoWriter::default();
+        encode(&mut proto);
+        proto.buf
+    }
+
+    #[test]
+    fn varints_use_seven_bits_per_byte() {
+        assert_eq!(encoded(|proto| proto.varint(0)), [0x00]);
+        assert_eq!(encoded(|proto| proto.varint(127)), [0x7f]);
+        assert_eq!(encoded(|proto| proto.varint(128)), [0x80, 0x01]);
+        assert_eq!(encoded(|proto| proto.varint(300)), [0xac, 0x02]);
+        let mut max = vec![0xff; 9];
+        max.push(0x01);
+        assert_eq!(encoded(|proto| proto.varint(u64::MAX)), max);
+    }
+
+    #[test]
+    fn keys_hold_the_field_and_the_wire_type() {
+        assert_eq!(encoded(|proto| proto.key(1, 0)), [0x08]);
+        assert_eq!(encoded(|proto| proto.key(2, 2)), [0x12]);
+        assert_eq!(encoded(|proto| proto.key(16, 0)), [0x80, 0x01]);
+        // Negative ints are sign-extended to ten bytes.
+        let mut minus_one = vec![0x08];
+        minus_one.extend_from_slice(&[0xff; 9]);
+        minus_one.push(0x01);
+        assert_eq!(encoded(|proto| proto.int(1, -1)), minus_one);
+        assert_eq!(
+            encoded(|proto| proto.message(7, |inner| inner.string(1, "ab"))),
+            [0x3a, 0x04, 0x0a, 0x02, b'a', b'b']
+        );
+    }
+
+    /// A net with one input, one output and the given activation, without random genes.
+    fn small_net(activation: Activation) -> Net {
+        let conf = TestConf { threads: 1 };
+        let mut innovs = InnovationRegistry::default();
+        let mut net = Net::new(1, 1, &mut innovs, &conf, &mut NeatRng::seed_from_u64(0));
+        net.links[0].weight = 0.5;
+        net.links[1].weight = -1.0;
+        net.nodes[2].activation = activation;
+        net
+    }
+
+    #[test]
+    fn small_net_matches_golden_bytes() {
+        let bytes = small_net(Activation::Identity).to_onnx().unwrap();
+
+        // The IR version and the producer, whose version follows the package.
+        let version = env!("CARGO_PKG_VERSION");
+        let mut header = vec![0x08, 0x07, 0x12, 0x04];
+        header.extend_from_slice(b"neat");
+        header.extend_from_slice(&[0x1a, version.len() as u8]);
+        header.extend_from_slice(version.as_bytes());
+        assert_eq!(bytes[..header.len()], header[..]);
+
+        // The graph and the opset import.
+        let hex: String = bytes[header.len()..]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        let golden = concat!(
+            "3aac030a320a06696e707574730a0a67305f77656967687473120967305f6d61746d756c1a096730",
+            "5f6d61746d756c22064d61744d756c0a310a0967305f6d61746d756c0a0f67305f626961735f7765",
+            "6967687473120667305f73756d1a0667305f73756d22034164640a220a0667305f73756d12066730",
+            "5f6163741a0667305f61637422084964656e746974790a390a06696e707574730a0667305f616374",
+            "120867305f73746174651a0867305f73746174652206436f6e6361742a0b0a04617869731801a001",
+            "020a410a0867305f73746174650a0e6f75747075745f636f6c756d6e7312076f7574707574731a07",
+            "6f75747075747322064761746865722a0b0a04617869731801a0010212036e65742a1c0801080110",
+            "0b420a67305f776569676874734a08000000000000e03f2a1f0801100b420f67305f626961735f77",
+            "6569676874734a08000000000000f0bf2a1e08011007420e6f75747075745f636f6c756d6e734a08",
+            "01000000000000005a1d0a06696e7075747312130a11080b120d0a07120562617463680a02080162",
+            "1e0a076f75747075747312130a11080b120d0a07120562617463680a02080142040a00100d",
+        );
+        assert_eq!(hex, golden);
+    }
+
+    /// Evaluates the graph with the semantics of the ONNX operators it uses.
+    fn run_graph(graph: &GraphBuilder, inputs: &[f64], inputs_count: usize) -> Vec<f64> {
+        let rows = inputs.len() / inputs_count;
+        let mut values = HashMap::<String, (Vec<usize>, Vec<f64>)>::new();
+        values.insert(
+            "inputs".to_string(),
+            (vec![rows, inputs_count], inputs.to_vec()),
+        );
+        for initializer in &graph.initializers {
+            let data = initializer
+                .raw_data
+                .chunks_exact(8)
+                .map(|bytes| {
+                    let bytes = bytes.try_into().unwrap();
+                    if initializer.data_type == DOUBLE {
+                        f64::from_le_bytes(bytes)
+                    } else {
+                        i64::from_le_bytes(bytes) as f64
+                    }
+                })
+                .collect();
+            let dims = initializer.dims.iter().map(|dim| *dim as usize).collect();
+            values.insert(initializer.name.clone(), (dims, data));
+        }
+
+        for node in &graph.nodes {
+            let (dims, x) = values[&node.inputs[0]].clone();
+            let binary = |op: fn(f64, f64) -> f64| {
+                let (_, y) = &values[&node.inputs[1]];
+                let width = *dims.last().unwrap();
+                let data: Vec<f64> = x
+                    .iter()
+                    .enumerate()
+                    .map(|(i, x)| match y.len() {
+                        1 => op(*x, y[0]),
+                        len if len == width => op(*x, y[i % width]),
+                        _ => op(*x, y[i]),
+                    })
+                    .collect();
+                (dims.clone(), data)
+            };
+            let unary = |op: fn(f64) -> f64| (dims.clone(), x.iter().map(|x| op(*x)).collect());
+            let out = match node.op_type {
+                "MatMul" => {
+                    let (weight_dims, weights) = &values[&node.inputs[1]];
+                    let (width, columns) = (weight_dims[0], weight_dims[1]);
+                    let mut data = vec![0.0; rows * columns];
+                    for row in 0..rows {
+                        for column in 0..columns {
+                            for k in 0..width {
+                                data[row * columns + column] +=
+                                    x[row * width + k] * weights[k * columns + column];
+                            }
+                        }
+                    }
+                    (vec![rows, columns], data)
+                }
+                "Add" => binary(|x, y| x + y),
+                "Mul" => binary(|x, y| x * y),
+                "Greater" => binary(|x, y| if y < x { 1.0 } else { 0.0 }),
+                "Cast" | "Identity" => unary(|x| x),
+                "Sigmoid" => unary(|x| 1.0 / (1.0 + (-x).exp())),
+                "Tanh" => unary(f64::tanh),
+                "Relu" => unary(|x| x.max(0.0)),
+                "Sin" => unary(f64::sin),
+                "Neg" => unary(|x| -x),
+                "Exp" => unary(f64::exp),
+                "Abs" => unary(f64::abs),
+                "Concat" => {
+                    let (dims2, y) = &values[&node.inputs[1]];
+                    let (width, width2) = (dims[1], dims2[1]);
+                    let mut data = Vec::with_capacity(rows * (width + width2));
+                    for row in 0..rows {
+                        data.extend_from_slice(&x[(row * width)..((row + 1) * width)]);
+                        data.extend_from_slice(&y[(row * width2)..((row + 1) * width2)]);
+                    }
+                    (vec![rows, width + width2], data)
+                }
+                "Gather" => {
+                    let (_, columns) = &values[&node.inputs[1]];
+                    let width = dims[1];
+                    let mut data = Vec::with_capacity(rows * columns.len());
+                    for row in 0..rows {
+                        for column in columns {
+                            data.push(x[row * width + *column as usize]);
+                        }
+                    }
+                    (vec![rows, columns.len()], data)
+                }
+                op_type => panic!("unexpected operator {}", op_type),
+            };
+            values.insert(node.output.clone(), out);
+        }
+        values.remove("outputs").unwrap().1
+    }
+
+    #[test]
+    fn graph_reproduces_compiled_eval() {
+        let activations = [
+            Activation::Sigmoid,
+            Activation::Tanh,
+            Activation::Relu,
+            Activation::Identity,
+            Activation::Sine,
+            Activation::Gaussian,
+            Activation::Abs,
+            Activation::Step,
+        ];
+        let conf = TestConf { threads: 1 };
+        let mut innovs = InnovationRegistry::default();
+        let mut rng = NeatRng::seed_from_u64(9);
+        let mut net = Net::new(3, 2, &mut innovs, &conf, &mut rng);
+        for _ in 0..80 {
+            net.mutate(&mut innovs, &conf, &mut rng);
+        }
+        for (i, node) in net.nodes.iter_mut().enumerate().skip(4) {
+            node.activation = activations[i % activations.len()];
+            node.bias = 0.1 * (i as f64);
+            node.response = 1.0 + 0.05 * (i as f64);
+        }
+
+        let mut compiled = CompiledNet::new(&net).unwrap();
+        let graph = compiled.onnx_graph().unwrap();
+        assert!(3 < graph.nodes.len());
+        let inputs: Vec<f64> = (0..15).map(|i| (i as f64 * 0.71).cos()).collect();
+        let outputs = run_graph(&graph, &inputs, 3);
+        for (row, row_outputs) in inputs.chunks(3).zip(outputs.chunks(2)) {
+            let expected = compiled.eval(row).unwrap();
+            for (output, expected) in row_outputs.iter().zip(expected) {
+                assert!(
+                    (output - expected).abs() < 1e-12,
+                    "{} != {}",
+                    output,
+                    expected
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,301 @@
+use crate::neat::{Activation, NeatError, Net};
+
+/// A weighted connection of a compiled network.
+#[derive(Clone, Copy)]
+pub struct CompiledLink {
+    /// The node the value is read from.
+    pub from: usize,
+    /// The weight of the link.
+    pub weight: f64,
+}
+
+/// A node of a compiled network, together with the range of its links in `CompiledNet::links`.
+#[derive(Clone, Copy)]
+pub struct CompiledNode {
+    /// The index of the node in the activation buffer.
+    pub index: usize,
+    /// The end of the node's links. The links start where the previous node's links end.
+    pub links_end: usize,
+    /// The activation function of the node.
+    pub activation: Activation,
+    pub bias: f64,
+    pub response: f64,
+}
+
+impl CompiledNode {
+    /// Applies the bias, the response and the activation function to the weighted sum of the inputs.
+    pub fn activate(&self, sum: f64) -> f64 {
+        self.activation.apply(self.bias + self.response * sum)
+    }
+}
+
+/// An immutable feed-forward phenotype of a `Net`.
+///
+/// The nodes are stored in topological order with their enabled links next to them, so evaluation
+/// is a single pass over two flat arrays. It needs neither the innovations nor any allocation.
+#[derive(Clone)]
+pub struct CompiledNet {
+    pub inputs_count: usize,
+    pub outputs_count: usize,
+    nodes: Vec<CompiledNode>,
+    links: Vec<CompiledLink>,
+    activations: Vec<f64>,
+    /// The activations of batched evaluation, one column of rows per node.
+    batch_activations: Vec<f64>,
+    batch_sums: Vec<f64>,
+}
+
+impl CompiledNet {
+    /// Compiles the network. Only the nodes that the outputs depend on are kept.
+    ///
+    /// # Errors
+    ///
+    /// Returns `NeatError::Recurrent` if the network is recurrent, and `NeatError::Cycle` if the
+    /// enabled links of the network form a cycle.
+    pub fn new(net: &Net) -> Result<Self, NeatError> {
+        if net.recurrent {
+            return Err(NeatError::Recurrent);
+        }
+
+        let outputs_start = net.inputs_count + 1;
+        let mut out = Self {
+            inputs_count: net.inputs_count,
+            outputs_count: net.outputs_count,
+            nodes: Vec::new(),
+            links: Vec::new(),
+            activations: vec![0.0; net.nodes.len()],
+            batch_activations: Vec::new(),
+            batch_sums: Vec::new(),
+        };
+        out.activations[net.inputs_count] = 1.0;
+
+        // 0: unvisited, 1: on the stack, 2: ordered.
+        let mut states = vec![0u8; net.nodes.len()];
+        let mut order = Vec::<usize>::with_capacity(net.nodes.len());
+        let mut stack = Vec::<(usize, usize)>::new();
+
+        for output in outputs_start..(outputs_start + net.outputs_count) {
+            if states[output] != 0 {
+                continue;
+            }
+            states[output] = 1;
+            stack.push((output, 0));
+
+            while let Some((node_index, next_link)) = stack.pop() {
+                let in_link_indices = &net.nodes[node_index].in_link_indices;
+                if next_link == in_link_indices.len() {
+                    states[node_index] = 2;
+                    order.push(node_index);
+                    continue;
+                }
+                stack.push((node_index, next_link + 1));
+
+                let link = &net.links[in_link_indices[next_link]];
+                let from = net.link_from(link);
+                if !link.enabled || from <= net.inputs_count {
+                    continue;
+                }
+                match states[from] {
+                    0 => {
+                        states[from] = 1;
+                        stack.push((from, 0));
+                    }
+                    1 => return Err(NeatError::Cycle),
+                    _ => {}
+                }
+            }
+        }
+
+        for node_index in order {
+            for link_index in &net.nodes[node_index].in_link_indices {
+                let link = &net.links[*link_index];
+                if link.enabled {
+                    out.links.push(CompiledLink {
+                        from: net.link_from(link),
+                        weight: link.weight,
+                    });
+                }
+            }
+            out.nodes.push(CompiledNode {
+                index: node_index,
+                links_end: out.links.len(),
+                activation: net.nodes[node_index].activation,
+                bias: net.nodes[node_index].bias,
+                response: net.nodes[node_index].response,
+            });
+        }
+
+        Ok(out)
+    }
+
+    /// The evaluation plan, in topological order.
+    pub fn nodes(&self) -> &[CompiledNode] {
+        &self.nodes
+    }
+
+    /// The enabled links, grouped by the node they lead to.
+    pub fn links(&self) -> &[CompiledLink] {
+        &self.links
+    }
+
+    /// Evaluates the network and returns the outputs. Gives the same results as `Net::eval`.
+    pub fn eval(&mut self, inputs: &[f64]) -> Result<&[f64], NeatError> {
+        if inputs.len() != self.inputs_count {
+            return Err(NeatError::InputLength {
+                expected: self.inputs_count,
+                actual: inputs.len(),
+            });
+        }
+
+        self.activations[..self.inputs_count].copy_from_slice(&inputs[..self.inputs_count]);
+
+        let mut links_start = 0;
+        for node in &self.nodes {
+            let mut sum = 0.0;
+            for link in &self.links[links_start..node.links_end] {
+                sum += self.activations[link.from] * link.weight;
+            }
+            self.activations[node.index] = node.activate(sum);
+            links_start = node.links_end;
+        }
+
+        let outputs_start = self.inputs_count + 1;
+        Ok(&self.activations[outputs_start..(outputs_start + self.outputs_count)])
+    }
+
+    /// Evaluates the network over many rows at once. `inputs` and `outputs` are row-major matrices
+    /// with `inputs_count` and `outputs_count` columns.
+    ///
+    /// Every node is evaluated once for all the rows, so the inner loops run over contiguous
+    /// columns and can be vectorized. The results are bit-identical to calling `eval` on every row.
+    ///
+    /// # Errors
+    ///
+    /// Returns `NeatError::InputLength` if the length of `inputs` isn't a multiple of `inputs_count`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `inputs` and `outputs` don't hold the same number of rows.
+    pub fn eval_batch(&mut self, inputs: &[f64], outputs: &mut [f64]) -> Result<(), NeatError> {
+        let rows = inputs
+            .len()
+            .checked_div(self.inputs_count)
+            .unwrap_or(outputs.len() / self.outputs_count.max(1));
+        if inputs.len() != rows * self.inputs_count {
+            return Err(NeatError::InputLength {
+                expected: rows * self.inputs_count,
+                actual: inputs.len(),
+            });
+        }
+        assert_eq!(
+            outputs.len(),
+            rows * self.outputs_count,
+            "Wrong output matrix size."
+        );
+
+        let Self {
+            inputs_count,
+            outputs_count,
+            nodes,
+            links,
+            activations,
+            batch_activations,
+            batch_sums,
+        } = self;
+        let (inputs_count, outputs_count) = (*inputs_count, *outputs_count);
+
+        batch_activations.clear();
+        batch_activations.resize(activations.len() * rows, 0.0);
+        batch_sums.clear();
+        batch_sums.resize(rows, 0.0);
+
+        for (row, row_inputs) in inputs.chunks_exact(inputs_count.max(1)).enumerate() {
+            for (i, input) in row_inputs.iter().enumerate() {
+                batch_activations[i * rows + row] = *input;
+            }
+        }
+        for bias in &mut batch_activations[(inputs_count * rows)..((inputs_count + 1) * rows)] {
+            *bias = 1.0;
+        }
+
+        let mut links_start = 0;
+        for node in nodes.iter() {
+            for sum in batch_sums.iter_mut() {
+                *sum = 0.0;
+            }
+            for link in &links[links_start..node.links_end] {
+                let column = &batch_activations[(link.from * rows)..((link.from + 1) * rows)];
+                for (sum, activation) in batch_sums.iter_mut().zip(column) {
+                    *sum += *activation * link.weight;
+                }
+            }
+
+            let column = &mut batch_activations[(node.index * rows)..((node.index + 1) * rows)];
+            for (activation, sum) in column.iter_mut().zip(batch_sums.iter()) {
+                *activation = node.activate(*sum);
+            }
+            links_start = node.links_end;
+        }
+
+        let outputs_start = inputs_count + 1;
+        for (row, row_outputs) in outputs.chunks_exact_mut(outputs_count.max(1)).enumerate() {
+            for (i, output) in row_outputs.iter_mut().enumerate() {
+                *output = batch_activations[(outputs_start + i) * rows + row];
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Net {
+    /// Compiles the network into a `CompiledNet` for fast repeated evaluation. Only feed-forward
+    /// networks can be compiled, see `CompiledNet::new`.
+    pub fn compile(&self) -> Result<CompiledNet, NeatError> {
+        CompiledNet::new(self)
+    }
+
+    /// Evaluates a feed-forward network over a row-major input matrix, see `CompiledNet::eval_batch`.
+    ///
+    /// The network is compiled on every call. To evaluate the same network repeatedly, `compile` it
+    /// once and call `CompiledNet::eval_batch` instead.
+    pub fn eval_batch(&self, inputs: &[f64], outputs: &mut [f64]) -> Result<(), NeatError> {
+        self.compile()?.eval_batch(inputs, outputs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use crate::neat::{InnovationRegistry, NeatError, NeatRng, Net, TestConf};
+
+    #[test]
+    fn eval_batch_matches_eval() {
+        let conf = TestConf { threads: 1 };
+        let mut innovs = InnovationRegistry::default();
+        let mut rng = NeatRng::seed_from_u64(3);
+        let mut net = Net::new(3, 2, &mut innovs, &conf, &mut rng);
+        for _ in 0..60 {
+            net.mutate(&mut innovs, &conf, &mut rng);
+        }
+
+        let inputs: Vec<f64> = (0..30).map(|i| (i as f64 * 0.37).sin()).collect();
+        let mut outputs = vec![0.0; 20];
+        net.eval_batch(&inputs, &mut outputs).unwrap();
+        for (row, row_outputs) in inputs.chunks(3).zip(outputs.chunks(2)) {
+            assert_eq!(net.eval(row).unwrap(), row_outputs);
+        }
+    }
+
+    #[test]
+    fn recurrent_net_isnt_compiled() {
+        let conf = TestConf { threads: 1 };
+        let mut innovs = InnovationRegistry::default();
+        let mut net = Net::new(2, 1, &mut innovs, &conf, &mut NeatRng::seed_from_u64(0));
+        net.recurrent = true;
+        assert!(matches!(net.compile(), Err(NeatError::Recurrent)));
+        let mut outputs = [0.0];
+        assert!(net.eval_batch(&[0.0, 1.0], &mut outputs).is_err());
+    }
+}
@@ -1,19 +1,82 @@
-use crate::neat::{Conf, Innov, Net, Species};
+use std::collections::HashSet;
+
+use rand::{Rng, SeedableRng};
+
+use crate::neat::evaluation::available_threads;
+use crate::neat::{Conf, InnovationRegistry, NeatError, Net, Species};
+
+/// The random number generator of a population. Every random decision of the evolution is drawn
+/// from it, so a seed determines the whole run.
+pub type NeatRng = rand_pcg::Pcg64;
+
+/// How a net of the next generation is made.
+enum Offspring {
+    /// An unchanged copy of this net.
+    Elite(usize),
+    /// A child bred with a generator of this seed.
+    Child(u64),
+}
 
 pub struct Pop {
     pub size: usize,
+    /// The number of generations bred so far.
+    pub generation: u32,
     pub nets: Vec<Net>,
     pub species: Vec<Species>,
-    pub innovs: Vec<Innov>,
+    /// The innovations that the nets and species use. The unused ones are dropped after every
+    /// generation.
+    pub innovs: InnovationRegistry,
+    /// The compatibility threshold of the next speciation. It only changes by itself when
+    /// `Conf::get_target_species_count` is set, and follows `Conf::get_compat_threshold` otherwise.
+    pub compat_threshold: f64,
+    pub rng: NeatRng,
 }
 
 impl Pop {
+    /// Creates a population with a randomly seeded generator.
     pub fn new(size: usize, inputs_count: usize, outputs_count: usize, conf: &dyn Conf) -> Self {
+        Self::with_rng(
+            size,
+            inputs_count,
+            outputs_count,
+            NeatRng::from_entropy(),
+            conf,
+        )
+    }
+
+    /// Creates a population whose evolution is fully determined by `seed`.
+    pub fn with_seed(
+        size: usize,
+        inputs_count: usize,
+        outputs_count: usize,
+        seed: u64,
+        conf: &dyn Conf,
+    ) -> Self {
+        Self::with_rng(
+            size,
+            inputs_count,
+            outputs_count,
+            NeatRng::seed_from_u64(seed),
+            conf,
+        )
+    }
+
+    /// Creates a population that draws its random decisions from `rng`.
+    pub fn with_rng(
+        size: usize,
+        inputs_count: usize,
+        outputs_count: usize,
+        rng: NeatRng,
+        conf: &dyn Conf,
+    ) -> Self {
         let mut out = Self {
-            size: size,
+            size,
+            generation: 0,
             nets: Vec::with_capacity(size),
             species: Vec::new(),
-            innovs: Vec::new(),
+            innovs: InnovationRegistry::new(conf.get_innovation_scope()),
+            compat_threshold: conf.get_compat_threshold(),
+            rng,
         };
 
         for _ in 0..size {
@@ -21,16 +84,39 @@ impl Pop {
                 inputs_count,
                 outputs_count,
                 &mut out.innovs,
-                0,
                 conf,
+                &mut out.rng,
             ));
         }
 
         out
     }
 
-    pub fn next_gen(&mut self, conf: &dyn Conf) {
-        let mut next_nets = Vec::<Net>::with_capacity(self.size);
+    /// Speciates the population and replaces it with the next generation.
+    ///
+    /// # Errors
+    ///
+    /// Returns `NeatError::NanFitness` before changing anything if a net has NaN fitness, so the
+    /// generation can be retried after fixing the fitness. Returns `NeatError::Extinct` if every
+    /// species was removed, which the population can't recover from. Degenerate fitness, such as
+    /// every net having zero fitness, isn't an error; the offspring are then split evenly between
+    /// the species and parents are chosen uniformly. A species with negative avarage fitness gets
+    /// no share of the offspring.
+    pub fn next_gen(&mut self, conf: &dyn Conf) -> Result<(), NeatError> {
+        self.breed(conf).map(|_| ())
+    }
+
+    /// `Pop::next_gen`, returning the species that were removed.
+    ///
+    /// Afterwards the members of every species are the nets that were bred from it.
+    pub(super) fn breed(&mut self, conf: &dyn Conf) -> Result<Vec<Species>, NeatError> {
+        if let Some(net) = self.nets.iter().position(|net| net.fitness.is_nan()) {
+            return Err(NeatError::NanFitness { net });
+        }
+
+        if conf.get_target_species_count().is_none() {
+            self.compat_threshold = conf.get_compat_threshold();
+        }
 
         for species in &mut self.species {
             species.clear();
@@ -39,31 +125,65 @@ impl Pop {
         for (i, net) in self.nets.iter_mut().enumerate() {
             net.in_species = false;
             for species in &mut self.species {
-                species.add_member(net, i, conf);
+                species.add_member(net, i, self.compat_threshold, conf);
                 if net.in_species {
                     break;
                 }
             }
 
+            // A net that founds a species is its first member, so a new champion isn't left out.
             if !net.in_species {
-                self.species.push(Species::new(net));
+                let mut species = Species::new(net);
+                species.members.push(i);
+                net.in_species = true;
+                self.species.push(species);
             }
         }
 
+        // The champion and its species are only tracked when they are kept.
+        let champion = if conf.keep_champion() {
+            (0..self.nets.len()).reduce(|best, i| {
+                if self.nets[best].fitness < self.nets[i].fitness {
+                    i
+                } else {
+                    best
+                }
+            })
+        } else {
+            None
+        };
+        let mut champion_species_index = None;
+
+        let fitnesses: Vec<f64> = self.nets.iter().map(|net| net.fitness).collect();
+        let adjusted_fitness = conf.get_fitness_transform().apply(&fitnesses);
+
         let mut species_avarage_fitness_sum = 0.0;
+        let mut non_finite_avarage_fitness = false;
         let mut best_species_index = 0;
         let mut best_species_avarage_fitness = 0.0;
         let mut bad_species = Vec::<usize>::new();
+        let mut species_sizes = Vec::<usize>::with_capacity(self.species.len());
 
         for (i, species) in self.species.iter_mut().enumerate() {
-            if species.members.len() == 0 || conf.get_staleness_threshold() <= species.staleness {
+            species_sizes.push(species.members.len());
+            let has_champion = champion.is_some_and(|champion| species.members.contains(&champion));
+            if has_champion {
+                champion_species_index = Some(i);
+            }
+            if species.members.is_empty()
+                || (conf.get_staleness_threshold() <= species.staleness && !has_champion)
+            {
                 bad_species.push(i);
                 continue;
             }
-            species.cull(&self.nets, conf);
-            species.fitness_sharing(&self.nets);
-            species.choose_random_repr(&self.nets);
-            species_avarage_fitness_sum += species.avarage_fitness;
+            species.cull(&self.nets, conf)?;
+            species.fitness_sharing(&self.nets, &adjusted_fitness);
+            species.choose_random_repr(&self.nets, &mut self.rng)?;
+            if species.avarage_fitness.is_finite() {
+                species_avarage_fitness_sum += species.avarage_fitness.max(0.0);
+            } else {
+                non_finite_avarage_fitness = true;
+            }
             if best_species_avarage_fitness < species.avarage_fitness {
                 best_species_index = i;
                 best_species_avarage_fitness = species.avarage_fitness;
@@ -71,38 +191,268 @@ impl Pop {
         }
 
         bad_species.reverse();
+        let mut extinct_species = Vec::with_capacity(bad_species.len());
         for i in bad_species {
-            self.species.remove(i);
+            extinct_species.push(self.species.remove(i));
+            species_sizes.remove(i);
             if i < best_species_index {
                 best_species_index -= 1;
             }
+            if let Some(champion_species_index) = &mut champion_species_index {
+                if i < *champion_species_index {
+                    *champion_species_index -= 1;
+                }
+            }
         }
 
-        let old_innovs_count = self.innovs.len();
+        if self.species.is_empty() {
+            return Err(NeatError::Extinct);
+        }
 
-        for species in &self.species {
-            let baby_count = (species.avarage_fitness / species_avarage_fitness_sum
-                * (self.size as f64))
-                .floor() as usize;
-            for _ in 0..baby_count {
-                next_nets.push(species.make_child(
-                    &mut self.innovs,
-                    old_innovs_count,
-                    &self.nets,
-                    conf,
-                ));
+        if let Some(target) = conf.get_target_species_count() {
+            let (min, max) = conf.get_compat_threshold_bounds();
+            let step = conf.get_compat_threshold_step();
+            if target < self.species.len() {
+                self.compat_threshold += step;
+            } else if self.species.len() < target {
+                self.compat_threshold -= step;
             }
+            self.compat_threshold = self.compat_threshold.max(min).min(max);
         }
 
-        while next_nets.len() < self.size {
-            next_nets.push(self.species[best_species_index].make_child(
-                &mut self.innovs,
-                old_innovs_count,
-                &self.nets,
-                conf,
-            ));
+        // The shares only add up to the population size if every avarage is finite and the
+        // negative ones count as zero.
+        let degenerate_fitness = non_finite_avarage_fitness
+            || !(species_avarage_fitness_sum.is_finite() && 0.0 < species_avarage_fitness_sum);
+
+        let mut baby_counts: Vec<usize> = self
+            .species
+            .iter()
+            .map(|species| {
+                if degenerate_fitness {
+                    self.size / self.species.len()
+                } else {
+                    let share = species.avarage_fitness.max(0.0) / species_avarage_fitness_sum;
+                    (share * (self.size as f64)).floor() as usize
+                }
+            })
+            .collect();
+        // The shares are rounded down, so they shouldn't add up to more than the population size,
+        // but a float error mustn't grow the population.
+        let mut allocated_baby_count = baby_counts.iter().sum::<usize>();
+        while self.size < allocated_baby_count {
+            let most = (0..baby_counts.len())
+                .max_by_key(|i| (baby_counts[*i], std::cmp::Reverse(*i)))
+                .unwrap();
+            baby_counts[most] -= 1;
+            allocated_baby_count -= 1;
+        }
+        // The offspring that the rounding left over go to the best species.
+        let mut extra_baby_count = self.size.saturating_sub(allocated_baby_count);
+
+        // The champion's species needs a baby to carry the champion. It's taken from the left over
+        // offspring, or from the species with the most offspring.
+        if let Some(champion_species_index) = champion_species_index {
+            if baby_counts[champion_species_index] == 0 && 0 < self.size {
+                if 0 < extra_baby_count {
+                    extra_baby_count -= 1;
+                } else {
+                    let most = (0..baby_counts.len())
+                        .max_by_key(|i| (baby_counts[*i], std::cmp::Reverse(*i)))
+                        .unwrap();
+                    baby_counts[most] -= 1;
+                }
+                baby_counts[champion_species_index] += 1;
+            }
+        }
+
+        // Every child gets the index of its species and the seed of its own generator, so the
+        // children don't depend on how they're split between threads. The culled members are
+        // sorted best first, so the elites are the first members.
+        let mut babies = Vec::<(usize, Offspring)>::with_capacity(self.size);
+        for (i, species) in self.species.iter().enumerate() {
+            let mut elites_count = if conf.get_species_elitism_min_size() <= species_sizes[i] {
+                conf.get_species_elitism()
+            } else {
+                0
+            };
+            if champion_species_index == Some(i) {
+                elites_count = elites_count.max(1);
+            }
+            elites_count = elites_count.min(baby_counts[i]).min(species.members.len());
+
+            for member in &species.members[..elites_count] {
+                babies.push((i, Offspring::Elite(*member)));
+            }
+            for _ in elites_count..baby_counts[i] {
+                babies.push((i, Offspring::Child(self.rng.gen())));
+            }
+        }
+        for _ in 0..extra_baby_count {
+            babies.push((best_species_index, Offspring::Child(self.rng.gen())));
+        }
+
+        self.innovs.start_generation(conf.get_innovation_scope());
+        let next_nets = self.reproduce(&babies, conf)?;
+
+        for species in &mut self.species {
+            species.clear();
+        }
+        for (i, (species_index, _)) in babies.iter().enumerate() {
+            self.species[*species_index].members.push(i);
         }
 
         self.nets = next_nets;
+        self.collect_innovations();
+        self.generation += 1;
+        extinct_species.reverse();
+        Ok(extinct_species)
+    }
+
+    /// Drops the innovations that neither a net nor a species representative uses anymore.
+    fn collect_innovations(&mut self) {
+        let used: HashSet<usize> = self
+            .nets
+            .iter()
+            .chain(self.species.iter().map(|species| &species.repr))
+            .flat_map(|net| net.links.iter().map(|link| link.innov))
+            .collect();
+        self.innovs.retain(|innov| used.contains(&innov.number));
+    }
+
+    /// Makes every baby, spread over `Conf::get_threads_count` threads.
+    ///
+    /// Every thread breeds into its own copy of the innovations. Their new innovations are pending
+    /// until the threads finish, then they are added in the order of the children, so the same
+    /// link added by several children in this generation gets a single number, just as
+    /// `Net::add_link` does when breeding on one thread.
+    fn reproduce(
+        &mut self,
+        babies: &[(usize, Offspring)],
+        conf: &dyn Conf,
+    ) -> Result<Vec<Net>, NeatError> {
+        let old_next_number = self.innovs.next_number();
+        let threads_count = available_threads(conf.get_threads_count());
+        let chunk_size = babies.len().div_ceil(threads_count).max(1);
+
+        let (species, nets, innovs) = (&self.species, &self.nets, &self.innovs);
+        let breed =
+            |chunk: &[(usize, Offspring)]| -> Result<(Vec<Net>, InnovationRegistry), NeatError> {
+                let mut local_innovs = innovs.clone();
+                let mut children = Vec::with_capacity(chunk.len());
+                for (species_index, offspring) in chunk {
+                    children.push(match offspring {
+                        Offspring::Elite(net) => {
+                            let mut elite = nets[*net].clone();
+                            elite.reset_state();
+                            elite
+                        }
+                        Offspring::Child(seed) => species[*species_index].make_child(
+                            &mut local_innovs,
+                            nets,
+                            conf,
+                            &mut NeatRng::seed_from_u64(*seed),
+                        )?,
+                    });
+                }
+                Ok((children, local_innovs))
+            };
+
+        let results = if threads_count == 1 {
+            vec![breed(babies)]
+        } else {
+            std::thread::scope(|scope| {
+                let handles: Vec<_> = babies
+                    .chunks(chunk_size)
+                    .map(|chunk| scope.spawn(move || breed(chunk)))
+                    .collect();
+                handles
+                    .into_iter()
+                    .map(|handle| {
+                        handle
+                            .join()
+                            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+                    })
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        // The pending innovations of a thread may connect nodes that split other pending
+        // innovations, whose ids change along with their numbers.
+        let fixed_nodes_count = self.nets.first().map_or(0, Net::fixed_nodes_count);
+        let old_ids_end = fixed_nodes_count + old_next_number;
+        let mut out = Vec::with_capacity(babies.len());
+        for result in results {
+            let (children, local_innovs) = result?;
+
+            let pending_innovs = local_innovs.since(old_next_number);
+            let mut numbers = Vec::with_capacity(pending_innovs.len());
+            for pending in pending_innovs {
+                let renumber_node = |id: usize| {
+                    if old_ids_end <= id {
+                        fixed_nodes_count + numbers[id - old_ids_end]
+                    } else {
+                        id
+                    }
+                };
+                let (from, to) = (renumber_node(pending.from), renumber_node(pending.to));
+                numbers.push(self.innovs.get_or_insert(from, to));
+            }
+
+            for mut child in children {
+                child.renumber_innovs(old_next_number, &numbers);
+                out.push(child);
+            }
+        }
+
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::neat::{NeatError, Net, Pop, Termination, TestConf};
+
+    /// Scores the nets by their output for a fixed input, spread over negative and positive values.
+    fn score(pop: &mut Pop) {
+        for net in &mut pop.nets {
+            let output = net.eval(&[1.0, 0.0]).unwrap()[0];
+            net.fitness = 20.0 * output - 10.0;
+        }
+    }
+
+    #[test]
+    fn negative_avarage_fitness_keeps_the_population_size() {
+        let conf = TestConf { threads: 1 };
+        let mut pop = Pop::with_seed(60, 2, 1, 11, &conf);
+        for _ in 0..10 {
+            score(&mut pop);
+            pop.next_gen(&conf).unwrap();
+            assert_eq!(pop.nets.len(), 60);
+        }
+    }
+
+    fn xor_fitness(net: &mut Net) -> Result<f64, NeatError> {
+        let mut outputs = [0.0; 4];
+        net.eval_batch(&[0.0, 1.0, 1.0, 0.0, 1.0, 1.0, 0.0, 0.0], &mut outputs)?;
+        let [net01, net10, net11, net00] = outputs;
+        Ok(4.0 - ((1.0 - net01).powi(2) + (1.0 - net10).powi(2) + net11.powi(2) + net00.powi(2)))
+    }
+
+    #[test]
+    fn thread_count_doesnt_change_the_run() {
+        let run = |threads| {
+            let conf = TestConf { threads };
+            let mut pop = Pop::with_seed(80, 2, 1, 7, &conf);
+            let termination = Termination {
+                max_generations: Some(8),
+                ..Termination::default()
+            };
+            pop.run(&xor_fitness, &termination, &mut [], &conf).unwrap();
+            assert!(0 < pop.innovs.generation_innovations_count());
+            // Holds the nets, the species, the innovations and the generator.
+            pop.to_checkpoint_bytes()
+        };
+        assert!(run(1) == run(4));
     }
 }
@@ -0,0 +1,120 @@
+use std::io::{self, Write};
+
+use crate::neat::{Checkpointer, GenerationStats, Net, Pop, Species};
+
+/// Hooks that `Pop::run` calls while it evolves the population.
+///
+/// An error stops the run with `NeatError::Io`.
+#[allow(unused_variables)]
+pub trait Reporter {
+    /// Called before the generation is evaluated.
+    fn generation_start(&mut self, pop: &Pop) -> io::Result<()> {
+        Ok(())
+    }
+    /// Called after the generation is evaluated, before the next one is bred.
+    fn generation_end(&mut self, pop: &Pop, stats: &GenerationStats) -> io::Result<()> {
+        Ok(())
+    }
+    /// Called for every species that was removed while breeding after `generation`.
+    fn species_extinct(&mut self, generation: u32, species: &Species) -> io::Result<()> {
+        Ok(())
+    }
+    /// Called when a net beats the best fitness of the run so far.
+    fn new_best(&mut self, generation: u32, net: &Net) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Prints a summary of every generation.
+#[derive(Default)]
+pub struct StdoutReporter;
+
+impl Reporter for StdoutReporter {
+    fn generation_end(&mut self, _pop: &Pop, stats: &GenerationStats) -> io::Result<()> {
+        let mut out = io::stdout().lock();
+        writeln!(out, "Gen {}", stats.generation)?;
+        writeln!(out, "\tspecies count: {}", stats.species.len())?;
+        writeln!(
+            out,
+            "\tavarage hidden nodes count: {}",
+            stats.hidden_nodes.mean
+        )?;
+        writeln!(out, "\tavarage links count: {}", stats.enabled_links.mean)?;
+        writeln!(out, "\tavarage fitness: {}", stats.fitness_mean)?;
+        writeln!(out, "\tbest fitness: {}", stats.fitness_max)
+    }
+}
+
+/// Writes one CSV row per generation. The per-species statistics are left out.
+pub struct CsvReporter<W: Write> {
+    out: W,
+    wrote_header: bool,
+}
+
+impl<W: Write> CsvReporter<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            wrote_header: false,
+        }
+    }
+}
+
+impl<W: Write> Reporter for CsvReporter<W> {
+    fn generation_end(&mut self, _pop: &Pop, stats: &GenerationStats) -> io::Result<()> {
+        if !self.wrote_header {
+            writeln!(
+                self.out,
+                "generation,fitness_mean,fitness_median,fitness_std_dev,fitness_max,species_count,\
+                 hidden_nodes_min,hidden_nodes_mean,hidden_nodes_max,\
+                 enabled_links_min,enabled_links_mean,enabled_links_max,new_innovations,\
+                 compat_threshold"
+            )?;
+            self.wrote_header = true;
+        }
+        writeln!(
+            self.out,
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            stats.generation,
+            stats.fitness_mean,
+            stats.fitness_median,
+            stats.fitness_std_dev,
+            stats.fitness_max,
+            stats.species.len(),
+            stats.hidden_nodes.min,
+            stats.hidden_nodes.mean,
+            stats.hidden_nodes.max,
+            stats.enabled_links.min,
+            stats.enabled_links.mean,
+            stats.enabled_links.max,
+            stats.new_innovations,
+            stats.compat_threshold
+        )?;
+        self.out.flush()
+    }
+}
+
+/// Writes every `GenerationStats` as a line of JSON.
+pub struct JsonLinesReporter<W: Write> {
+    out: W,
+}
+
+impl<W: Write> JsonLinesReporter<W> {
+    pub fn new(out: W) -> Self {
+        Self { out }
+    }
+}
+
+impl<W: Write> Reporter for JsonLinesReporter<W> {
+    fn generation_end(&mut self, _pop: &Pop, stats: &GenerationStats) -> io::Result<()> {
+        serde_json::to_writer(&mut self.out, stats)?;
+        writeln!(self.out)?;
+        self.out.flush()
+    }
+}
+
+impl Reporter for Checkpointer {
+    fn generation_start(&mut self, pop: &Pop) -> io::Result<()> {
+        self.checkpoint(pop).map(|_| ())
+    }
+}
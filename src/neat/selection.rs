@@ -0,0 +1,220 @@
+use rand::prelude::*;
+use rand_distr::Uniform;
+
+use crate::neat::{NeatError, Net, Species};
+
+/// Chooses the parents of a child from the members of a species, see `Conf::get_selection_strategy`.
+///
+/// The members are sorted best first when `Species::make_child` is called, and strategies compare
+/// them by `Species::members_shared_fitness`.
+pub trait SelectionStrategy: Send + Sync {
+    /// Chooses `count` members, which may repeat, and returns their indices into `nets`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `NeatError::EmptySpecies` if there are no members, and
+    /// `NeatError::DegenerateFitness` if the fitness isn't shared between the current members.
+    /// Fitness-proportionate strategies also return `NeatError::DegenerateFitness` if the shared
+    /// fitness doesn't sum to a positive number. `Species::make_child` then chooses uniformly.
+    fn select(
+        &self,
+        species: &Species,
+        nets: &[Net],
+        count: usize,
+        rng: &mut dyn RngCore,
+    ) -> Result<Vec<usize>, NeatError>;
+}
+
+/// Fitness-proportionate selection with an independent spin of the wheel for every parent.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Roulette;
+
+impl SelectionStrategy for Roulette {
+    fn select(
+        &self,
+        species: &Species,
+        _nets: &[Net],
+        count: usize,
+        rng: &mut dyn RngCore,
+    ) -> Result<Vec<usize>, NeatError> {
+        if count == 2 {
+            let (p1, p2) = species.choose_parents(rng)?;
+            return Ok(vec![p1, p2]);
+        }
+        (0..count).map(|_| species.choose_parent(rng)).collect()
+    }
+}
+
+/// Picks the best of `size` members drawn uniformly, for every parent. Larger tournaments select
+/// more strongly.
+#[derive(Clone, Copy, Debug)]
+pub struct Tournament {
+    pub size: usize,
+}
+
+impl Default for Tournament {
+    fn default() -> Self {
+        Self { size: 3 }
+    }
+}
+
+impl SelectionStrategy for Tournament {
+    fn select(
+        &self,
+        species: &Species,
+        _nets: &[Net],
+        count: usize,
+        rng: &mut dyn RngCore,
+    ) -> Result<Vec<usize>, NeatError> {
+        check_shared(species)?;
+
+        let uniform = Uniform::new(0, species.members.len());
+        let mut out = Vec::with_capacity(count);
+        for _ in 0..count {
+            let mut winner = uniform.sample(rng);
+            for _ in 1..self.size {
+                let contender = uniform.sample(rng);
+                if species.members_shared_fitness[winner]
+                    < species.members_shared_fitness[contender]
+                {
+                    winner = contender;
+                }
+            }
+            out.push(species.members[winner]);
+        }
+        Ok(out)
+    }
+}
+
+/// Linear rank selection. The worst of `n` members has weight 1 and the best has weight `n`, so
+/// only the order of the fitness matters.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Rank;
+
+impl SelectionStrategy for Rank {
+    fn select(
+        &self,
+        species: &Species,
+        _nets: &[Net],
+        count: usize,
+        rng: &mut dyn RngCore,
+    ) -> Result<Vec<usize>, NeatError> {
+        check_shared(species)?;
+
+        let ranked = ranked_members(species);
+        let len = ranked.len();
+        let weights_sum = len * (len + 1) / 2;
+        let uniform = Uniform::new(0, weights_sum);
+
+        let mut out = Vec::with_capacity(count);
+        for _ in 0..count {
+            let mut target = uniform.sample(rng);
+            for (rank, member) in ranked.iter().enumerate() {
+                let weight = len - rank;
+                if target < weight {
+                    out.push(species.members[*member]);
+                    break;
+                }
+                target -= weight;
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Chooses uniformly among the best `fraction` of the members, and at least the best one.
+#[derive(Clone, Copy, Debug)]
+pub struct Truncation {
+    pub fraction: f64,
+}
+
+impl Default for Truncation {
+    fn default() -> Self {
+        Self { fraction: 0.5 }
+    }
+}
+
+impl SelectionStrategy for Truncation {
+    fn select(
+        &self,
+        species: &Species,
+        _nets: &[Net],
+        count: usize,
+        rng: &mut dyn RngCore,
+    ) -> Result<Vec<usize>, NeatError> {
+        check_shared(species)?;
+
+        let ranked = ranked_members(species);
+        let kept = ((self.fraction * (ranked.len() as f64)).ceil() as usize).clamp(1, ranked.len());
+        let uniform = Uniform::new(0, kept);
+        Ok((0..count)
+            .map(|_| species.members[ranked[uniform.sample(rng)]])
+            .collect())
+    }
+}
+
+/// Fitness-proportionate selection with a single spin of a wheel with `count` equally spaced
+/// pointers, so the parents spread over the members more evenly than with `Roulette`.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct StochasticUniversalSampling;
+
+impl SelectionStrategy for StochasticUniversalSampling {
+    fn select(
+        &self,
+        species: &Species,
+        _nets: &[Net],
+        count: usize,
+        rng: &mut dyn RngCore,
+    ) -> Result<Vec<usize>, NeatError> {
+        check_shared(species)?;
+        let total = species.avarage_fitness;
+        if !(total.is_finite() && 0.0 < total) {
+            return Err(NeatError::DegenerateFitness {
+                avarage_fitness: total,
+            });
+        }
+        if count == 0 {
+            return Ok(Vec::new());
+        }
+
+        let spacing = total / (count as f64);
+        let mut pointer = Uniform::new(0.0, spacing).sample(rng);
+        let mut shared_fitness_sum = 0.0;
+        let mut out = Vec::with_capacity(count);
+        for (member, shared_fitness) in species.members.iter().zip(&species.members_shared_fitness)
+        {
+            shared_fitness_sum += shared_fitness;
+            while out.len() < count && pointer < shared_fitness_sum {
+                out.push(*member);
+                pointer += spacing;
+            }
+        }
+        // Rounding can leave the last pointers just past the end of the wheel.
+        while out.len() < count {
+            out.push(*species.members.last().unwrap());
+        }
+        Ok(out)
+    }
+}
+
+/// Checks that the species has members and that their fitness is shared.
+fn check_shared(species: &Species) -> Result<(), NeatError> {
+    if species.members.is_empty() {
+        Err(NeatError::EmptySpecies)
+    } else if species.members_shared_fitness.len() != species.members.len() {
+        Err(NeatError::DegenerateFitness {
+            avarage_fitness: species.avarage_fitness,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// The positions of the members in `Species::members`, best shared fitness first.
+fn ranked_members(species: &Species) -> Vec<usize> {
+    let mut out: Vec<usize> = (0..species.members.len()).collect();
+    out.sort_by(|a, b| {
+        species.members_shared_fitness[*b].total_cmp(&species.members_shared_fitness[*a])
+    });
+    out
+}
@@ -0,0 +1,745 @@
+use std::{fmt, fs, io, path::Path};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::neat::{Activation, InnovationRegistry, Net, Species};
+
+use super::neuralnet::{Link, Node};
+
+/// The version of the file format. Files with a different version are rejected.
+pub const FORMAT_VERSION: u32 = 4;
+
+/// The first bytes of the binary format, followed by the version as a little endian `u32`.
+const BINARY_MAGIC: &[u8; 4] = b"NEAT";
+
+/// The error returned when a file can't be loaded.
+///
+/// Species representatives are counted as nets after the stored nets, so the representative of
+/// species `i` is net `nets.len() + i`.
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    /// The file isn't valid JSON or bincode, or doesn't have the expected fields.
+    Syntax(String),
+    /// The binary file doesn't start with the magic bytes.
+    BadHeader,
+    UnsupportedVersion(u32),
+    UnknownActivation(String),
+    /// The innovations aren't sorted by number, or the innovation table is otherwise inconsistent.
+    BadInnovation(usize),
+    /// A node isn't stored at its own index, or the net has fewer nodes than inputs, bias and outputs.
+    NodeIndexOutOfRange {
+        net: usize,
+        node: usize,
+    },
+    /// An input, the bias or an output doesn't have its index as id, or the hidden nodes aren't
+    /// sorted by id.
+    BadNodeId {
+        net: usize,
+        node: usize,
+    },
+    /// A link connects a node id that the net doesn't have.
+    UnknownNode {
+        net: usize,
+        node: usize,
+    },
+    /// A node refers to a link that doesn't exist.
+    LinkIndexOutOfRange {
+        net: usize,
+        link: usize,
+    },
+    /// A link refers to an innovation that isn't in the innovation table.
+    MissingInnovation {
+        net: usize,
+        innov: usize,
+    },
+    /// A link doesn't end in the node that lists it, isn't listed exactly once, or connects other
+    /// nodes than its innovation in the innovation table.
+    InconsistentLinks {
+        net: usize,
+        link: usize,
+    },
+    /// The links of a net that isn't recurrent form a cycle, counting the disabled ones.
+    Cycle {
+        net: usize,
+    },
+    /// A species member isn't in the stored nets.
+    MemberOutOfRange {
+        species: usize,
+        member: usize,
+    },
+    /// A species has shared fitness values, but not one for every member.
+    SharedFitnessCount {
+        species: usize,
+        count: usize,
+    },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(err) => write!(f, "io error: {}", err),
+            LoadError::Syntax(msg) => write!(f, "syntax error: {}", msg),
+            LoadError::BadHeader => write!(f, "not a NEAT binary file"),
+            LoadError::UnsupportedVersion(version) => {
+                write!(f, "unsupported format version {}", version)
+            }
+            LoadError::UnknownActivation(name) => write!(f, "unknown activation `{}`", name),
+            LoadError::BadInnovation(innov) => {
+                write!(f, "innovation {} is inconsistent", innov)
+            }
+            LoadError::NodeIndexOutOfRange { net, node } => {
+                write!(f, "net {}: node index {} out of range", net, node)
+            }
+            LoadError::BadNodeId { net, node } => {
+                write!(f, "net {}: node {} has a bad id", net, node)
+            }
+            LoadError::UnknownNode { net, node } => {
+                write!(f, "net {}: unknown node id {}", net, node)
+            }
+            LoadError::LinkIndexOutOfRange { net, link } => {
+                write!(f, "net {}: link index {} out of range", net, link)
+            }
+            LoadError::MissingInnovation { net, innov } => {
+                write!(f, "net {}: missing innovation {}", net, innov)
+            }
+            LoadError::InconsistentLinks { net, link } => {
+                write!(
+                    f,
+                    "net {}: link {} is inconsistent with the nodes",
+                    net, link
+                )
+            }
+            LoadError::Cycle { net } => {
+                write!(
+                    f,
+                    "net {}: the links of a feed-forward net form a cycle",
+                    net
+                )
+            }
+            LoadError::MemberOutOfRange { species, member } => {
+                write!(f, "species {}: member {} out of range", species, member)
+            }
+            LoadError::SharedFitnessCount { species, count } => write!(
+                f,
+                "species {}: {} shared fitness values don't match the members",
+                species, count
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<io::Error> for LoadError {
+    fn from(err: io::Error) -> Self {
+        LoadError::Io(err)
+    }
+}
+
+/// The storage format of an `Archive`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Format {
+    /// Pretty-printed JSON.
+    Json,
+    /// The magic bytes and the version, followed by bincode.
+    Binary,
+}
+
+/// Nets and species together with their innovation table.
+///
+/// Nets don't need the table to be evaluated, so a single champion can also be stored on its own
+/// with `Net::to_json`.
+pub struct Archive {
+    pub innovs: InnovationRegistry,
+    pub nets: Vec<Net>,
+    /// Species members are indices into `nets`.
+    pub species: Vec<Species>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct NodeData {
+    index: usize,
+    id: usize,
+    in_link_indices: Vec<usize>,
+    activation: String,
+    bias: f64,
+    response: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct LinkData {
+    innov: usize,
+    from: usize,
+    to: usize,
+    weight: f64,
+    enabled: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct NetData {
+    inputs_count: usize,
+    outputs_count: usize,
+    recurrent: bool,
+    #[serde(with = "non_finite")]
+    fitness: f64,
+    nodes: Vec<NodeData>,
+    links: Vec<LinkData>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct SpeciesData {
+    members: Vec<usize>,
+    #[serde(with = "non_finite_vec")]
+    members_shared_fitness: Vec<f64>,
+    repr: NetData,
+    staleness: u32,
+    #[serde(with = "non_finite")]
+    best_fitness: f64,
+    #[serde(with = "non_finite")]
+    avarage_fitness: f64,
+}
+
+/// A float that human-readable formats store as `"inf"`, `"-inf"` or `"NaN"` when it isn't
+/// finite, since JSON numbers can't hold those values.
+struct Float(f64);
+
+impl Serialize for Float {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() && !self.0.is_finite() {
+            serializer.serialize_str(&self.0.to_string())
+        } else {
+            serializer.serialize_f64(self.0)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Float {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Number(f64),
+            Text(String),
+        }
+
+        if !deserializer.is_human_readable() {
+            return f64::deserialize(deserializer).map(Float);
+        }
+        match Repr::deserialize(deserializer)? {
+            Repr::Number(value) => Ok(Float(value)),
+            Repr::Text(text) => match text.parse::<f64>() {
+                Ok(value) if !value.is_finite() => Ok(Float(value)),
+                _ => Err(de::Error::custom(format!("invalid float `{}`", text))),
+            },
+        }
+    }
+}
+
+/// Stores an `f64` field as a `Float`.
+mod non_finite {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+        Float(*value).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+        Float::deserialize(deserializer).map(|value| value.0)
+    }
+}
+
+/// Stores a `Vec<f64>` field as `Float`s.
+mod non_finite_vec {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(values: &[f64], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(values.iter().map(|value| Float(*value)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<f64>, D::Error> {
+        let values = Vec::<Float>::deserialize(deserializer)?;
+        Ok(values.into_iter().map(|value| value.0).collect())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct ArchiveData {
+    innovs: InnovationRegistry,
+    nets: Vec<NetData>,
+    species: Vec<SpeciesData>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonFile {
+    format_version: u32,
+    #[serde(flatten)]
+    data: ArchiveData,
+}
+
+#[derive(Serialize, Deserialize)]
+struct NetJsonFile {
+    format_version: u32,
+    #[serde(flatten)]
+    net: NetData,
+}
+
+impl NetData {
+    pub(super) fn new(net: &Net) -> Self {
+        Self {
+            inputs_count: net.inputs_count,
+            outputs_count: net.outputs_count,
+            recurrent: net.recurrent,
+            fitness: net.fitness,
+            nodes: net
+                .nodes
+                .iter()
+                .map(|node| NodeData {
+                    index: node.index,
+                    id: node.id,
+                    in_link_indices: node.in_link_indices.clone(),
+                    activation: node.activation.name().to_string(),
+                    bias: node.bias,
+                    response: node.response,
+                })
+                .collect(),
+            links: net
+                .links
+                .iter()
+                .map(|link| LinkData {
+                    innov: link.innov,
+                    from: link.from,
+                    to: link.to,
+                    weight: link.weight,
+                    enabled: link.enabled,
+                })
+                .collect(),
+        }
+    }
+
+    /// Validates the net and builds it. `net_index` is only used for error reporting.
+    pub(super) fn into_net(
+        self,
+        net_index: usize,
+        custom_activations: &[Activation],
+    ) -> Result<Net, LoadError> {
+        let nodes_count = self.nodes.len();
+        if nodes_count < self.inputs_count + 1 + self.outputs_count {
+            return Err(LoadError::NodeIndexOutOfRange {
+                net: net_index,
+                node: self.inputs_count + self.outputs_count,
+            });
+        }
+
+        let fixed_nodes_count = self.inputs_count + 1 + self.outputs_count;
+        for (i, node) in self.nodes.iter().enumerate() {
+            let id_in_order = if i < fixed_nodes_count {
+                node.id == i
+            } else {
+                self.nodes[i - 1].id < node.id
+            };
+            if !id_in_order {
+                return Err(LoadError::BadNodeId {
+                    net: net_index,
+                    node: i,
+                });
+            }
+        }
+        let has_node = |id: usize| self.nodes.binary_search_by_key(&id, |node| node.id).is_ok();
+
+        for link in &self.links {
+            for id in [link.from, link.to] {
+                if !has_node(id) {
+                    return Err(LoadError::UnknownNode {
+                        net: net_index,
+                        node: id,
+                    });
+                }
+            }
+        }
+
+        let mut listed = vec![false; self.links.len()];
+        let mut nodes = Vec::with_capacity(nodes_count);
+        for (i, node) in self.nodes.into_iter().enumerate() {
+            if node.index != i {
+                return Err(LoadError::NodeIndexOutOfRange {
+                    net: net_index,
+                    node: node.index,
+                });
+            }
+            for link_index in &node.in_link_indices {
+                let link = self
+                    .links
+                    .get(*link_index)
+                    .ok_or(LoadError::LinkIndexOutOfRange {
+                        net: net_index,
+                        link: *link_index,
+                    })?;
+                if link.to != node.id || listed[*link_index] {
+                    return Err(LoadError::InconsistentLinks {
+                        net: net_index,
+                        link: *link_index,
+                    });
+                }
+                listed[*link_index] = true;
+            }
+
+            let activation = Activation::from_name(&node.activation, custom_activations)
+                .ok_or(LoadError::UnknownActivation(node.activation))?;
+            nodes.push(Node {
+                in_link_indices: node.in_link_indices,
+                index: i,
+                id: node.id,
+                activation,
+                bias: node.bias,
+                response: node.response,
+            });
+        }
+        if let Some(link) = listed.iter().position(|listed| !listed) {
+            return Err(LoadError::InconsistentLinks {
+                net: net_index,
+                link,
+            });
+        }
+
+        let net = Net {
+            inputs_count: self.inputs_count,
+            outputs_count: self.outputs_count,
+            nodes,
+            links: self
+                .links
+                .into_iter()
+                .map(|link| Link {
+                    innov: link.innov,
+                    from: link.from,
+                    to: link.to,
+                    weight: link.weight,
+                    enabled: link.enabled,
+                })
+                .collect(),
+            in_species: false,
+            fitness: self.fitness,
+            recurrent: self.recurrent,
+            activations: Vec::new(),
+        };
+        if !net.recurrent && net.has_cycle() {
+            return Err(LoadError::Cycle { net: net_index });
+        }
+        Ok(net)
+    }
+}
+
+impl SpeciesData {
+    pub(super) fn new(species: &Species) -> Self {
+        Self {
+            members: species.members.clone(),
+            members_shared_fitness: species.members_shared_fitness.clone(),
+            repr: NetData::new(&species.repr),
+            staleness: species.staleness,
+            best_fitness: species.best_fitness,
+            avarage_fitness: species.avarage_fitness,
+        }
+    }
+
+    /// Validates the species and builds it. Members must be smaller than `nets_count`.
+    pub(super) fn into_species(
+        self,
+        species_index: usize,
+        nets_count: usize,
+        custom_activations: &[Activation],
+    ) -> Result<Species, LoadError> {
+        if let Some(member) = self.members.iter().find(|member| nets_count <= **member) {
+            return Err(LoadError::MemberOutOfRange {
+                species: species_index,
+                member: *member,
+            });
+        }
+        let shared_fitness_count = self.members_shared_fitness.len();
+        if shared_fitness_count != 0 && shared_fitness_count != self.members.len() {
+            return Err(LoadError::SharedFitnessCount {
+                species: species_index,
+                count: shared_fitness_count,
+            });
+        }
+
+        Ok(Species {
+            members: self.members,
+            members_shared_fitness: self.members_shared_fitness,
+            repr: self
+                .repr
+                .into_net(nets_count + species_index, custom_activations)?,
+            staleness: self.staleness,
+            best_fitness: self.best_fitness,
+            avarage_fitness: self.avarage_fitness,
+        })
+    }
+}
+
+impl ArchiveData {
+    pub(super) fn new(innovs: &InnovationRegistry, nets: &[Net], species: &[Species]) -> Self {
+        Self {
+            innovs: innovs.clone(),
+            nets: nets.iter().map(NetData::new).collect(),
+            species: species.iter().map(SpeciesData::new).collect(),
+        }
+    }
+
+    pub(super) fn into_archive(
+        self,
+        custom_activations: &[Activation],
+    ) -> Result<Archive, LoadError> {
+        self.innovs.validate().map_err(LoadError::BadInnovation)?;
+
+        let Self {
+            innovs,
+            nets,
+            species,
+        } = self;
+        let nets_count = nets.len();
+        let nets = nets
+            .into_iter()
+            .enumerate()
+            .map(|(i, net)| net.into_net(i, custom_activations))
+            .collect::<Result<Vec<_>, _>>()?;
+        let species = species
+            .into_iter()
+            .enumerate()
+            .map(|(i, species)| species.into_species(i, nets_count, custom_activations))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let all_nets = nets
+            .iter()
+            .chain(species.iter().map(|species| &species.repr));
+        for (i, net) in all_nets.enumerate() {
+            for (j, link) in net.links.iter().enumerate() {
+                let innov = innovs.get(link.innov).ok_or(LoadError::MissingInnovation {
+                    net: i,
+                    innov: link.innov,
+                })?;
+                if innov.from != link.from || innov.to != link.to {
+                    return Err(LoadError::InconsistentLinks { net: i, link: j });
+                }
+            }
+        }
+
+        Ok(Archive {
+            innovs,
+            nets,
+            species,
+        })
+    }
+}
+
+impl Archive {
+    pub fn new(innovs: InnovationRegistry, nets: Vec<Net>, species: Vec<Species>) -> Self {
+        Self {
+            innovs,
+            nets,
+            species,
+        }
+    }
+
+    fn data(&self) -> ArchiveData {
+        ArchiveData::new(&self.innovs, &self.nets, &self.species)
+    }
+
+    /// Serializes the archive to pretty-printed JSON with a `format_version` field.
+    pub fn to_json(&self) -> String {
+        let file = JsonFile {
+            format_version: FORMAT_VERSION,
+            data: self.data(),
+        };
+        serde_json::to_string_pretty(&file).expect("Nets are always serializable.")
+    }
+
+    /// Serializes the archive to the compact binary format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = BINARY_MAGIC.to_vec();
+        out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        bincode::serialize_into(&mut out, &self.data()).expect("Nets are always serializable.");
+        out
+    }
+
+    /// Loads and validates an archive from JSON. Custom activations are looked up by name in
+    /// `custom_activations`.
+    pub fn from_json(json: &str, custom_activations: &[Activation]) -> Result<Self, LoadError> {
+        check_json_version(json)?;
+        let file: JsonFile =
+            serde_json::from_str(json).map_err(|err| LoadError::Syntax(err.to_string()))?;
+        file.data.into_archive(custom_activations)
+    }
+
+    /// Loads and validates an archive from the binary format.
+    pub fn from_bytes(bytes: &[u8], custom_activations: &[Activation]) -> Result<Self, LoadError> {
+        let payload = read_binary_header(bytes, BINARY_MAGIC)?;
+        let data: ArchiveData =
+            bincode::deserialize(payload).map_err(|err| LoadError::Syntax(err.to_string()))?;
+        data.into_archive(custom_activations)
+    }
+
+    /// Writes the archive to a file.
+    pub fn save(&self, path: impl AsRef<Path>, format: Format) -> io::Result<()> {
+        match format {
+            Format::Json => fs::write(path, self.to_json()),
+            Format::Binary => fs::write(path, self.to_bytes()),
+        }
+    }
+
+    /// Reads an archive from a file. The format is detected from the header.
+    pub fn load(
+        path: impl AsRef<Path>,
+        custom_activations: &[Activation],
+    ) -> Result<Self, LoadError> {
+        let bytes = fs::read(path)?;
+        if bytes.starts_with(BINARY_MAGIC) {
+            Self::from_bytes(&bytes, custom_activations)
+        } else {
+            let json =
+                String::from_utf8(bytes).map_err(|err| LoadError::Syntax(err.to_string()))?;
+            Self::from_json(&json, custom_activations)
+        }
+    }
+}
+
+impl Net {
+    /// Serializes the net on its own to pretty-printed JSON with a `format_version` field.
+    pub fn to_json(&self) -> String {
+        let file = NetJsonFile {
+            format_version: FORMAT_VERSION,
+            net: NetData::new(self),
+        };
+        serde_json::to_string_pretty(&file).expect("Nets are always serializable.")
+    }
+
+    /// Loads and validates a net stored with `Net::to_json`. Custom activations are looked up by
+    /// name in `custom_activations`.
+    pub fn from_json(json: &str, custom_activations: &[Activation]) -> Result<Self, LoadError> {
+        check_json_version(json)?;
+        let file: NetJsonFile =
+            serde_json::from_str(json).map_err(|err| LoadError::Syntax(err.to_string()))?;
+        file.net.into_net(0, custom_activations)
+    }
+}
+
+/// Checks the `format_version` field of a JSON file.
+fn check_json_version(json: &str) -> Result<(), LoadError> {
+    #[derive(Deserialize)]
+    struct Header {
+        format_version: u32,
+    }
+
+    let header: Header =
+        serde_json::from_str(json).map_err(|err| LoadError::Syntax(err.to_string()))?;
+    if header.format_version != FORMAT_VERSION {
+        return Err(LoadError::UnsupportedVersion(header.format_version));
+    }
+    Ok(())
+}
+
+/// Checks the magic bytes and the version of a binary file, and returns the rest of it.
+pub(super) fn read_binary_header<'a>(
+    bytes: &'a [u8],
+    magic: &[u8; 4],
+) -> Result<&'a [u8], LoadError> {
+    if bytes.len() < 8 || !bytes.starts_with(magic) {
+        return Err(LoadError::BadHeader);
+    }
+    let mut version = [0u8; 4];
+    version.copy_from_slice(&bytes[4..8]);
+    let version = u32::from_le_bytes(version);
+    if version != FORMAT_VERSION {
+        return Err(LoadError::UnsupportedVersion(version));
+    }
+    Ok(&bytes[8..])
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use crate::neat::{Archive, InnovationRegistry, LoadError, NeatRng, Net, Species, TestConf};
+
+    #[test]
+    fn feed_forward_cycle_is_rejected() {
+        let conf = TestConf { threads: 1 };
+        let mut innovs = InnovationRegistry::default();
+        let mut rng = NeatRng::seed_from_u64(0);
+        let mut net = Net::new(2, 1, &mut innovs, &conf, &mut rng);
+        net.mutate_node(&mut innovs, &conf, &mut rng);
+        // The split link ends in the output, so linking the output back to the new node is a cycle.
+        net.add_link(&mut innovs, 1.0, 3, 4);
+
+        let result = Net::from_json(&net.to_json(), &[]);
+        assert!(matches!(result, Err(LoadError::Cycle { net: 0 })));
+
+        net.recurrent = true;
+        assert!(Net::from_json(&net.to_json(), &[]).is_ok());
+    }
+
+    #[test]
+    fn non_finite_fitness_round_trips_through_json() {
+        let conf = TestConf { threads: 1 };
+        let mut innovs = InnovationRegistry::default();
+        let mut net = Net::new(2, 1, &mut innovs, &conf, &mut NeatRng::seed_from_u64(0));
+        net.fitness = f64::INFINITY;
+        assert_eq!(
+            Net::from_json(&net.to_json(), &[]).unwrap().fitness,
+            f64::INFINITY
+        );
+
+        net.fitness = f64::NAN;
+        let mut species = Species::new(&net);
+        species.members.push(0);
+        species.members_shared_fitness.push(f64::NEG_INFINITY);
+        species.avarage_fitness = f64::NAN;
+        let archive = Archive::new(innovs, vec![net], vec![species]);
+        let loaded = Archive::from_json(&archive.to_json(), &[]).unwrap();
+        assert!(loaded.nets[0].fitness.is_nan());
+        let species = &loaded.species[0];
+        assert_eq!(species.members_shared_fitness, [f64::NEG_INFINITY]);
+        assert_eq!(species.best_fitness, f64::NEG_INFINITY);
+        assert!(species.avarage_fitness.is_nan());
+        assert_eq!(archive.to_json(), loaded.to_json());
+    }
+
+    #[test]
+    fn archive_links_need_their_innovations() {
+        let conf = TestConf { threads: 1 };
+        let mut innovs = InnovationRegistry::default();
+        let net = Net::new(2, 1, &mut innovs, &conf, &mut NeatRng::seed_from_u64(0));
+
+        let archive = Archive::new(InnovationRegistry::default(), vec![net.clone()], Vec::new());
+        let result = Archive::from_json(&archive.to_json(), &[]);
+        assert!(matches!(
+            result,
+            Err(LoadError::MissingInnovation { net: 0, innov: 0 })
+        ));
+
+        let archive = Archive::new(innovs, vec![net], Vec::new());
+        assert!(Archive::from_bytes(&archive.to_bytes(), &[]).is_ok());
+    }
+
+    #[test]
+    fn shared_fitness_needs_one_value_per_member() {
+        let conf = TestConf { threads: 1 };
+        let mut innovs = InnovationRegistry::default();
+        let net = Net::new(2, 1, &mut innovs, &conf, &mut NeatRng::seed_from_u64(0));
+        let mut species = Species::new(&net);
+        species.members.push(0);
+        let archive = Archive::new(innovs.clone(), vec![net.clone()], vec![species]);
+        assert!(Archive::from_bytes(&archive.to_bytes(), &[]).is_ok());
+
+        let mut species = Species::new(&net);
+        species.members.push(0);
+        species.members_shared_fitness = vec![1.0, 2.0];
+        let archive = Archive::new(innovs, vec![net], vec![species]);
+        let result = Archive::from_bytes(&archive.to_bytes(), &[]);
+        assert!(matches!(
+            result,
+            Err(LoadError::SharedFitnessCount {
+                species: 0,
+                count: 2
+            })
+        ));
+    }
+}
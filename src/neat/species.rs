@@ -1,4 +1,6 @@
-use crate::neat::{Conf, Innov, Net, Pop};
+use rand::RngCore;
+
+use crate::neat::{Conf, InnovationRegistry, NeatError, Net, Pop};
 
 pub struct Species {
     pub members: Vec<usize>,
@@ -16,21 +18,28 @@ impl Species {
             members_shared_fitness: Vec::new(),
             repr: net.clone(),
             staleness: 0,
-            best_fitness: 0.0,
+            best_fitness: f64::NEG_INFINITY,
             avarage_fitness: 0.0,
         }
     }
 
-    pub fn choose_random_repr(&mut self, nets: &Vec<Net>) {
-        use rand::{prelude::*, thread_rng};
-        use rand_distr::Uniform;
-
-        let repr_pop_index =
-            self.members[Uniform::new(0, self.members.len()).sample(&mut thread_rng())];
-        self.repr = nets[repr_pop_index].clone();
+    pub fn choose_random_repr(
+        &mut self,
+        nets: &[Net],
+        rng: &mut dyn RngCore,
+    ) -> Result<(), NeatError> {
+        self.repr = nets[self.choose_uniform_parent(rng)?].clone();
+        Ok(())
     }
 
-    pub fn add_member(&mut self, net: &mut Net, net_index: usize, conf: &dyn Conf) {
+    /// Adds the net if it's closer to the representative than `compat_threshold`.
+    pub fn add_member(
+        &mut self,
+        net: &mut Net,
+        net_index: usize,
+        compat_threshold: f64,
+        conf: &dyn Conf,
+    ) {
         if !net.in_species {
             let mut disjoint = 0i32;
             let mut matching = 0i32;
@@ -54,19 +63,42 @@ impl Species {
 
             let excess = net.links.len() + self.repr.links.len() - i - j;
 
+            // Counted over the nodes that both nets have, matched by id.
+            let mut activation_diff = 0usize;
+            let (mut i, mut j) = (net.inputs_count + 1, net.inputs_count + 1);
+            while i < net.nodes.len() && j < self.repr.nodes.len() {
+                let (node, repr_node) = (&net.nodes[i], &self.repr.nodes[j]);
+                if node.id < repr_node.id {
+                    i += 1;
+                } else if repr_node.id < node.id {
+                    j += 1;
+                } else {
+                    if node.activation != repr_node.activation {
+                        activation_diff += 1;
+                    }
+                    i += 1;
+                    j += 1;
+                }
+            }
+
             let size_norm = conf.size_norm(net.links.len(), self.repr.links.len());
             let compat = (conf.get_excess_coef() * (excess as f64)
                 + conf.get_disjoint_coef() * (disjoint as f64))
                 / size_norm
-                + conf.get_weight_diff_coef() * weight_diff_sum / (matching as f64);
-            if compat < conf.get_compat_threshold() {
+                + conf.get_weight_diff_coef() * weight_diff_sum / (matching as f64)
+                + conf.get_activation_diff_coef() * (activation_diff as f64);
+            if compat < compat_threshold {
                 self.members.push(net_index);
                 net.in_species = true;
             }
         }
     }
 
-    pub fn fitness_sharing(&mut self, nets: &Vec<Net>) {
+    /// Shares the fitness between the members and updates the staleness.
+    ///
+    /// `adjusted_fitness` is the fitness of every net after `Conf::get_fitness_transform`. It's
+    /// what gets shared, while the staleness follows the raw fitness.
+    pub fn fitness_sharing(&mut self, nets: &[Net], adjusted_fitness: &[f64]) {
         self.staleness += 1;
         self.members_shared_fitness.resize(self.members.len(), 0.0);
         self.avarage_fitness = 0.0;
@@ -78,41 +110,55 @@ impl Species {
                 self.staleness = 0;
                 self.best_fitness = net.fitness;
             }
-            self.members_shared_fitness[i] = net.fitness / (self.members.len() as f64);
+            self.members_shared_fitness[i] =
+                adjusted_fitness[self.members[i]] / (self.members.len() as f64);
             self.avarage_fitness += self.members_shared_fitness[i];
         }
     }
 
-    pub fn choose_parent(&self) -> usize {
-        use rand::{prelude::*, thread_rng};
+    /// Chooses a member with probability proportional to its shared fitness.
+    ///
+    /// # Errors
+    ///
+    /// Returns `NeatError::DegenerateFitness` if the shared fitness doesn't sum to a positive
+    /// number or isn't shared between the current members yet, and `NeatError::EmptySpecies` if
+    /// there are no members.
+    pub fn choose_parent(&self, rng: &mut dyn RngCore) -> Result<usize, NeatError> {
+        use rand::prelude::*;
         use rand_distr::Uniform;
 
-        let to_parent_shared_fitness_sum =
-            Uniform::new(0.0, self.avarage_fitness).sample(&mut thread_rng());
+        self.check_selectable()?;
+
+        let to_parent_shared_fitness_sum = Uniform::new(0.0, self.avarage_fitness).sample(rng);
         let mut shared_fitness_sum = 0.0;
 
         for i in 0..self.members_shared_fitness.len() {
             shared_fitness_sum += self.members_shared_fitness[i];
             if to_parent_shared_fitness_sum < shared_fitness_sum {
-                return self.members[i];
+                return Ok(self.members[i]);
             }
         }
-        panic!("Parent choosing error.");
+        Err(NeatError::DegenerateFitness {
+            avarage_fitness: self.avarage_fitness,
+        })
     }
 
-    pub fn choose_parents(&self) -> (usize, usize) {
-        use rand::{prelude::*, thread_rng};
+    /// Chooses two members with probability proportional to their shared fitness.
+    ///
+    /// # Errors
+    ///
+    /// The same as `Species::choose_parent`.
+    pub fn choose_parents(&self, rng: &mut dyn RngCore) -> Result<(usize, usize), NeatError> {
+        use rand::prelude::*;
         use rand_distr::Uniform;
 
-        let mut to_p1_shared_fitness_sum =
-            Uniform::new(0.0, self.avarage_fitness).sample(&mut thread_rng());
-        let mut to_p2_shared_fitness_sum =
-            Uniform::new(0.0, self.avarage_fitness).sample(&mut thread_rng());
+        self.check_selectable()?;
+
+        let mut to_p1_shared_fitness_sum = Uniform::new(0.0, self.avarage_fitness).sample(rng);
+        let mut to_p2_shared_fitness_sum = Uniform::new(0.0, self.avarage_fitness).sample(rng);
 
         if to_p2_shared_fitness_sum < to_p1_shared_fitness_sum {
-            let tmp = to_p1_shared_fitness_sum;
-            to_p1_shared_fitness_sum = to_p2_shared_fitness_sum;
-            to_p2_shared_fitness_sum = tmp;
+            std::mem::swap(&mut to_p1_shared_fitness_sum, &mut to_p2_shared_fitness_sum);
         }
 
         let mut shared_fitness_sum = 0.0;
@@ -125,19 +171,55 @@ impl Species {
                 p1 = i;
             }
             if to_p2_shared_fitness_sum < shared_fitness_sum {
-                return (self.members[p1], self.members[i]);
+                return Ok((self.members[p1], self.members[i]));
             }
         }
-        panic!(
-            "Parents choosing error. sum: {}, p1: {}, p2: {}, avg: {}",
-            shared_fitness_sum,
-            to_p1_shared_fitness_sum,
-            to_p2_shared_fitness_sum,
-            self.avarage_fitness
-        );
+        Err(NeatError::DegenerateFitness {
+            avarage_fitness: self.avarage_fitness,
+        })
     }
 
-    pub fn cull(&mut self, nets: &Vec<Net>, conf: &dyn Conf) {
+    /// Chooses a member uniformly. This is the fallback when the fitness is degenerate.
+    pub fn choose_uniform_parent(&self, rng: &mut dyn RngCore) -> Result<usize, NeatError> {
+        use rand::prelude::*;
+        use rand_distr::Uniform;
+
+        if self.members.is_empty() {
+            return Err(NeatError::EmptySpecies);
+        }
+        Ok(self.members[Uniform::new(0, self.members.len()).sample(rng)])
+    }
+
+    /// Checks that fitness-proportionate selection is possible.
+    fn check_selectable(&self) -> Result<(), NeatError> {
+        if self.members.is_empty() {
+            Err(NeatError::EmptySpecies)
+        } else if self.avarage_fitness.is_finite()
+            && 0.0 < self.avarage_fitness
+            && self.members_shared_fitness.len() == self.members.len()
+        {
+            Ok(())
+        } else {
+            Err(NeatError::DegenerateFitness {
+                avarage_fitness: self.avarage_fitness,
+            })
+        }
+    }
+
+    /// Removes the worst members, keeping `Conf::get_cull_survival_percentage` of them.
+    ///
+    /// # Errors
+    ///
+    /// Returns `NeatError::NanFitness` if a member has NaN fitness. The members are left unchanged.
+    pub fn cull(&mut self, nets: &[Net], conf: &dyn Conf) -> Result<(), NeatError> {
+        if let Some(member) = self
+            .members
+            .iter()
+            .find(|member| nets[**member].fitness.is_nan())
+        {
+            return Err(NeatError::NanFitness { net: *member });
+        }
+
         self.members
             .sort_unstable_by(|a: &usize, b: &usize| -> std::cmp::Ordering {
                 (-nets[*a].fitness)
@@ -149,36 +231,50 @@ impl Species {
             (conf.get_cull_survival_percentage() * (self.members.len() as f64)).ceil() as usize,
             0,
         );
+        Ok(())
     }
 
+    /// Makes a mutated child of one or two members.
+    ///
+    /// Parents are chosen by `Conf::get_selection_strategy`, or uniformly when it fails because the
+    /// fitness is degenerate.
+    ///
+    /// # Errors
+    ///
+    /// Returns `NeatError::EmptySpecies` if there are no members.
     pub fn make_child(
         &self,
-        innovs: &mut Vec<Innov>,
-        old_innovs_count: usize,
-        nets: &Vec<Net>,
+        innovs: &mut InnovationRegistry,
+        nets: &[Net],
         conf: &dyn Conf,
-    ) -> Net {
-        use rand::{prelude::*, thread_rng};
+        rng: &mut dyn RngCore,
+    ) -> Result<Net, NeatError> {
+        use rand::prelude::*;
         use rand_distr::Uniform;
 
-        let mut out: Net;
-        if Uniform::from(0.0..1.0).sample(&mut thread_rng()) < conf.get_crossover_prob() {
-            let (p1, p2) = self.choose_parents();
-            if p1 != p2 {
-                out = nets[p1].crossover(&nets[p2], conf);
-            } else {
-                out = nets[p1].clone();
-            }
+        let crossover = Uniform::from(0.0..1.0).sample(rng) < conf.get_crossover_prob();
+        let count = if crossover { 2 } else { 1 };
+        let parents = match conf.get_selection_strategy().select(self, nets, count, rng) {
+            Err(NeatError::DegenerateFitness { .. }) => (0..count)
+                .map(|_| self.choose_uniform_parent(rng))
+                .collect::<Result<Vec<_>, _>>()?,
+            parents => parents?,
+        };
+
+        let mut out = if crossover && parents[0] != parents[1] {
+            nets[parents[0]].crossover(&nets[parents[1]], conf, rng)
         } else {
-            let p = self.choose_parent();
-            out = nets[p].clone();
-        }
+            nets[parents[0]].clone()
+        };
 
-        out.mutate(innovs, old_innovs_count, conf);
-        out
+        out.mutate(innovs, conf, rng);
+        out.reset_state();
+        Ok(out)
     }
 
+    /// Removes the members and their shared fitness.
     pub fn clear(&mut self) {
         self.members.clear();
+        self.members_shared_fitness.clear();
     }
 }
@@ -0,0 +1,107 @@
+use serde::Serialize;
+
+use crate::neat::Pop;
+
+/// A summary of the evaluated generation, see `Pop::stats`.
+#[derive(Clone, Debug, Serialize)]
+pub struct GenerationStats {
+    pub generation: u32,
+    pub fitness_mean: f64,
+    pub fitness_median: f64,
+    /// The population standard deviation of the fitness.
+    pub fitness_std_dev: f64,
+    pub fitness_max: f64,
+    /// The species that the current generation was bred from.
+    pub species: Vec<SpeciesStats>,
+    pub hidden_nodes: SizeStats,
+    pub enabled_links: SizeStats,
+    /// The number of innovations that were created while breeding the current generation.
+    pub new_innovations: usize,
+    /// The compatibility threshold of the next speciation, see `Pop::compat_threshold`.
+    pub compat_threshold: f64,
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct SpeciesStats {
+    /// The number of nets of the current generation that were bred from the species.
+    pub size: usize,
+    pub staleness: u32,
+    pub best_fitness: f64,
+}
+
+/// The distribution of a genome size over the population.
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct SizeStats {
+    pub min: usize,
+    pub mean: f64,
+    pub max: usize,
+}
+
+impl SizeStats {
+    fn new(sizes: impl Iterator<Item = usize>) -> Self {
+        let mut out = Self {
+            min: usize::MAX,
+            mean: 0.0,
+            max: 0,
+        };
+        let mut count = 0;
+        for size in sizes {
+            out.min = out.min.min(size);
+            out.max = out.max.max(size);
+            out.mean += size as f64;
+            count += 1;
+        }
+
+        if count == 0 {
+            out.min = 0;
+        } else {
+            out.mean /= count as f64;
+        }
+        out
+    }
+}
+
+impl Pop {
+    /// Summarizes the current generation. Call it after setting the fitness of the nets and before
+    /// `Pop::next_gen`.
+    pub fn stats(&self) -> GenerationStats {
+        let mut fitnesses: Vec<f64> = self.nets.iter().map(|net| net.fitness).collect();
+        let count = fitnesses.len() as f64;
+        let fitness_mean = fitnesses.iter().sum::<f64>() / count;
+        fitnesses.sort_unstable_by(|a, b| a.total_cmp(b));
+
+        let fitness_variance = fitnesses
+            .iter()
+            .map(|fitness| (fitness - fitness_mean) * (fitness - fitness_mean))
+            .sum::<f64>()
+            / count;
+        let fitness_median = match fitnesses.len() {
+            0 => f64::NAN,
+            len if len % 2 == 0 => (fitnesses[len / 2 - 1] + fitnesses[len / 2]) / 2.0,
+            len => fitnesses[len / 2],
+        };
+
+        GenerationStats {
+            generation: self.generation,
+            fitness_mean,
+            fitness_median,
+            fitness_std_dev: fitness_variance.sqrt(),
+            fitness_max: fitnesses.last().copied().unwrap_or(f64::NAN),
+            species: self
+                .species
+                .iter()
+                .map(|species| SpeciesStats {
+                    size: species.members.len(),
+                    staleness: species.staleness,
+                    best_fitness: species.best_fitness,
+                })
+                .collect(),
+            hidden_nodes: SizeStats::new(self.nets.iter().map(|net| net.get_hidden_nodes_count())),
+            enabled_links: SizeStats::new(
+                self.nets.iter().map(|net| net.get_enabled_links_count()),
+            ),
+            new_innovations: self.innovs.generation_innovations_count(),
+            compat_threshold: self.compat_threshold,
+        }
+    }
+}